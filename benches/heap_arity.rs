@@ -0,0 +1,98 @@
+//! Compares binary heaps with 4-ary and 8-ary ones on insert-heavy,
+//! extract-heavy and decrease_key-heavy workloads.
+//! Run with `cargo bench --bench heap_arity`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use the_algorithm_design_manual::heap::{Heap, HeapHandle, IndexedHeap, NaturalOrder};
+
+const ELEMENTS: usize = 1_000_000;
+const RUNS: u32 = 5;
+
+// A small xorshift generator, so the benchmark needs no dependencies and
+// every run sees the same data.
+fn random_values(count: usize, mut state: u64) -> Vec<u64> {
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        values.push(state);
+    }
+    values
+}
+
+// Run f RUNS times and return the fastest time.
+fn time<F>(mut f: F) -> Duration
+    where F: FnMut()
+{
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
+// Insert every value, then extract a tenth of them.
+fn insert_heavy<const D: usize>(values: &[u64]) -> Duration {
+    time(|| {
+        let mut heap: Heap<u64, NaturalOrder, D> = Heap::new(false).into_arity::<D>();
+        for value in values {
+            heap.insert(*value);
+        }
+        for _ in 0..values.len() / 10 {
+            black_box(heap.extract());
+        }
+    })
+}
+
+// Build the heap in one step, then extract everything.
+fn extract_heavy<const D: usize>(values: &[u64]) -> Duration {
+    time(|| {
+        let mut heap: Heap<u64, NaturalOrder, D> = values.iter().copied().collect();
+        while let Some(value) = heap.extract() {
+            black_box(value);
+        }
+    })
+}
+
+// Insert every value, lower each one twice, then extract everything.
+fn decrease_key_heavy<const D: usize>(values: &[u64]) -> Duration {
+    time(|| {
+        let mut heap = IndexedHeap::new(false).into_arity::<D>();
+        let handles: Vec<HeapHandle<u64>> = values.iter().map(|v| heap.insert(*v)).collect();
+        for round in 1..=2 {
+            for handle in handles.iter() {
+                let lowered = heap.get(*handle).unwrap() / (round + 1);
+                heap.decrease_key(*handle, lowered);
+            }
+        }
+        while let Some(value) = heap.extract() {
+            black_box(value);
+        }
+    })
+}
+
+fn main() {
+    let values = random_values(ELEMENTS, 0x2545_f491_4f6c_dd1d);
+    println!("{} elements, best of {} runs", ELEMENTS, RUNS);
+    println!("{:<20}{:>12}{:>12}{:>12}", "workload", "d = 2", "d = 4", "d = 8");
+
+    let rows = [
+        ("insert-heavy", [insert_heavy::<2>(&values), insert_heavy::<4>(&values),
+            insert_heavy::<8>(&values)]),
+        ("extract-heavy", [extract_heavy::<2>(&values), extract_heavy::<4>(&values),
+            extract_heavy::<8>(&values)]),
+        ("decrease_key-heavy", [decrease_key_heavy::<2>(&values),
+            decrease_key_heavy::<4>(&values), decrease_key_heavy::<8>(&values)])
+    ];
+    for (name, times) in rows {
+        print!("{:<20}", name);
+        for t in times {
+            print!("{:>10.1}ms", t.as_secs_f64() * 1000.0);
+        }
+        println!();
+    }
+}
@@ -1,4 +1,13 @@
+use crate::encoding::{self, Decode, DecodeError, Encode};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::ops;
+#[cfg(debug_assertions)]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 
 #[cfg(test)]
 mod arena_tests {
@@ -8,141 +17,1727 @@ mod arena_tests {
     #[should_panic(expected = "index out of bounds")]
     fn remove_from_empty() {
         let mut arena: Arena<i32> = Arena::new();
-        arena.remove(0);
+        arena.remove(Key::from_raw_parts(0, 0));
     }
 
     #[test]
     #[should_panic(expected = "index out of bounds")]
     fn index_when_empty() {
         let arena: Arena<i32> = Arena::new();
-        arena[0];
+        let _ = arena[Key::from_raw_parts(0, 0)];
     }
 
     #[test]
     fn insert_and_remove() {
         let mut arena = Arena::new();
-        arena.insert(0);
-        assert_eq!(arena.remove(0), Some(0));
-        assert!(arena.remove(0).is_none());
+        let key = arena.insert(0);
+        assert_eq!(arena.remove(key), Some(0));
+        assert!(arena.remove(key).is_none());
 
-        let mut indices = Vec::new();
+        let mut keys = Vec::new();
         for i in 0..100 {
-            indices.push(arena.insert(i));
+            keys.push(arena.insert(i));
         }
-        let mut j = 0;
-        for i in indices {
-            assert_eq!(arena.remove(i), Some(j));
-            j += 1;
+        for (j, h) in keys.into_iter().enumerate() {
+            assert_eq!(arena.remove(h), Some(j));
         }
     }
 
     #[test]
     fn insert_and_index() {
         let mut arena = Arena::new();
-        arena.insert(0);
-        assert_eq!(arena[0], Some(0));
+        let key = arena.insert(0);
+        assert_eq!(arena[key], 0);
 
-        let mut indices = Vec::new();
+        let mut keys = Vec::new();
         for i in 0..100 {
-            indices.push(arena.insert(i));
+            keys.push(arena.insert(i));
         }
-        let mut j = 0;
-        for i in indices {
-            assert_eq!(arena[i], Some(j));
-            j += 1;
+        for (j, h) in keys.into_iter().enumerate() {
+            assert_eq!(arena[h], j);
         }
     }
 
     #[test]
-    #[should_panic(expected = "Index out of bounds. Cannot borrow.")]
+    #[should_panic(expected = "index out of bounds")]
     fn borrow_mutable_out_of_bounds() {
         let mut arena: Arena<i32> = Arena::new();
-        arena.borrow_mutable(0);
+        arena.borrow_mutable(Key::from_raw_parts(0, 0));
     }
 
     #[test]
     fn mutate_stored_data() {
         let mut arena = Arena::new();
-        let index = arena.insert(String::from("Some data"));
-        let mutable = arena.borrow_mutable(0);
+        let key = arena.insert(String::from("Some data"));
+        let mutable = arena.borrow_mutable(key);
         *(mutable.unwrap()) = String::from("Some other data");
-        assert_eq!(arena[index].as_ref().unwrap(), "Some other data");
+        assert_eq!(arena[key], "Some other data");
+    }
+
+    // A key to a removed element must not reach whatever is later stored
+    // in the same slot.
+    #[test]
+    fn stale_handle() {
+        let mut arena = Arena::new();
+        let old = arena.insert(1);
+        arena.remove(old);
+        let new = arena.insert(2);
+
+        // The slot was reused, but under a new generation.
+        assert_eq!(old.index(), new.index());
+        assert_ne!(old.generation(), new.generation());
+
+        assert!(arena.borrow(old).is_none());
+        assert!(arena.borrow_mutable(old).is_none());
+        assert!(arena.remove(old).is_none());
+        assert_eq!(arena[new], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "key is stale")]
+    fn index_with_stale_handle() {
+        let mut arena = Arena::new();
+        let old = arena.insert(1);
+        arena.remove(old);
+        arena.insert(2);
+        let _ = arena[old];
+    }
+
+    // The non-panicking accessors should tell the three kinds of bad key
+    // apart instead of panicking.
+    #[test]
+    fn checked_access() {
+        let mut arena = Arena::new();
+        let never_allocated = Key::from_raw_parts(5, 0);
+        let removed = arena.insert(1);
+        arena.remove(removed);
+        let vacant = arena.insert(2);
+        arena.remove(vacant);
+        let live = arena.insert(3);
+
+        assert_eq!(arena.get(live), Some(&3));
+        assert!(arena.get(never_allocated).is_none());
+        assert!(arena.get(removed).is_none());
+        assert!(arena.get_mut(vacant).is_none());
+        assert!(arena.contains(live));
+        assert!(!arena.contains(never_allocated));
+        assert!(!arena.contains(removed));
+
+        assert_eq!(arena.try_remove(never_allocated), Err(ArenaError::NeverAllocated));
+        assert_eq!(arena.try_remove(removed), Err(ArenaError::Stale));
+        assert_eq!(arena.try_remove(live), Ok(3));
+        assert_eq!(arena.try_remove(live), Err(ArenaError::Vacant));
+    }
+
+    #[test]
+    fn index_mut() {
+        let mut arena = Arena::new();
+        let key = arena.insert(String::from("Some data"));
+        arena[key] = String::from("Some other data");
+        arena[key].push('!');
+        assert_eq!(arena[key], "Some other data!");
+    }
+
+    #[test]
+    #[should_panic(expected = "key refers to an empty slot")]
+    fn index_mut_vacant() {
+        let mut arena = Arena::new();
+        let key = arena.insert(0);
+        arena.remove(key);
+        arena[key] = 1;
+    }
+
+    // Iteration should skip vacant slots and report the key of each element.
+    #[test]
+    fn iterate() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        for h in keys.iter().step_by(2) {
+            arena.remove(*h);
+        }
+
+        let odd: Vec<(Key<i32>, i32)> = keys.iter().skip(1).step_by(2).
+            map(|h| (*h, arena[*h])).collect();
+        assert_eq!(arena.iter().map(|(h, i)| (h, *i)).collect::<Vec<_>>(), odd);
+        assert_eq!(arena.indices().collect::<Vec<_>>(),
+            odd.iter().map(|(h, _)| *h).collect::<Vec<_>>());
+
+        for (_, i) in arena.iter_mut() {
+            *i *= 10;
+        }
+        assert_eq!(arena.iter().map(|(_, i)| *i).collect::<Vec<_>>(),
+            vec![10, 30, 50, 70, 90]);
+    }
+
+    #[test]
+    fn retain() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        arena.retain(|_, i| {
+            *i += 1;
+            *i % 2 == 0
+        });
+
+        assert_eq!(arena.iter().map(|(_, i)| *i).collect::<Vec<_>>(),
+            vec![2, 4, 6, 8, 10]);
+        // Removed elements can't be reached through their old keys.
+        assert!(arena.borrow(keys[0]).is_none());
+        assert_eq!(arena[keys[1]], 2);
+
+        // Freed slots are reused before the arena grows.
+        for i in 0..5 {
+            assert!(arena.insert(i).index() < 10);
+        }
+        assert_eq!(arena.insert(5).index(), 10);
+    }
+
+    #[test]
+    fn drain() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        arena.remove(keys[3]);
+
+        let drained: Vec<i32> = arena.drain().map(|(_, i)| i).collect();
+        assert_eq!(drained, vec![0, 1, 2, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(arena.iter().count(), 0);
+        assert!(arena.borrow(keys[0]).is_none());
+
+        // Dropping a partially consumed drain still empties the arena.
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        assert!(keys.iter().all(|h| h.index() < 10));
+        arena.drain().next();
+        assert_eq!(arena.iter().count(), 0);
+    }
+
+    // Compaction should move the live elements to the front, keep their order,
+    // and hand back a table that translates the old keys.
+    #[test]
+    fn compact() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<usize>> = (0..10).map(|i| arena.insert(i)).collect();
+        for h in keys.iter().filter(|h| h.index() % 3 != 0) {
+            arena.remove(*h);
+        }
+
+        let remapping = arena.compact();
+        assert_eq!(arena.iter().map(|(_, i)| *i).collect::<Vec<_>>(), vec![0, 3, 6, 9]);
+        for (i, old) in keys.iter().enumerate() {
+            match remapping.get(*old) {
+                Some(new) => {
+                    assert_eq!(i % 3, 0);
+                    assert_eq!(new.index(), i / 3);
+                    assert_eq!(arena[new], i);
+                },
+                None => assert_ne!(i % 3, 0)
+            }
+            // Every key given out before compaction is now stale.
+            assert!(arena.get(*old).is_none());
+        }
+
+        // New slots don't revive the old keys either.
+        for i in 0..10 {
+            arena.insert(i);
+        }
+        assert!(keys.iter().all(|h| arena.get(*h).is_none()));
+    }
+
+    // The hook passed to compact_with() should see every live element along
+    // with the finished remapping table.
+    #[test]
+    fn compact_with_links() {
+        struct Linked {
+            value: i32,
+            link: Option<Key<Linked>>
+        }
+
+        let mut arena = Arena::new();
+        let garbage = arena.insert(Linked { value: 0, link: None });
+        let first = arena.insert(Linked { value: 1, link: None });
+        let second = arena.insert(Linked { value: 2, link: Some(first) });
+        arena.remove(garbage);
+
+        let remapping = arena.compact_with(|linked, remapping| {
+            linked.link = linked.link.map(|h| remapping.get(h).unwrap());
+        });
+        let second = remapping.get(second).unwrap();
+        let first = arena[second].link.unwrap();
+        assert_eq!(arena[first].value, 1);
+        assert_eq!(first.index(), 0);
+    }
+
+    // A key can only be used with the arena that created it. The check only
+    // happens in debug builds.
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "The key was created by a different arena.")]
+    fn key_from_other_arena() {
+        let mut arena = Arena::new();
+        let mut other = Arena::new();
+        arena.insert(0);
+        let key = other.insert(1);
+        arena.get(key);
+    }
+
+    // Keys rebuilt from their raw parts aren't tied to any arena.
+    #[test]
+    fn raw_key_equality() {
+        let mut arena = Arena::new();
+        let key = arena.insert(0);
+        let raw = Key::from_raw_parts(key.index(), key.generation());
+        assert_eq!(key, raw);
+        assert_eq!(arena[raw], 0);
+    }
+
+    #[test]
+    fn size_and_capacity() {
+        let mut arena = Arena::with_capacity(10);
+        assert!(arena.capacity() >= 10);
+        assert!(arena.is_empty());
+
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        for key in &keys[5..] {
+            arena.remove(*key);
+        }
+        assert_eq!(arena.len(), 5);
+        assert_eq!(arena.vacant_count(), 5);
+
+        // Vacant slots count towards the reservation.
+        arena.reserve(8);
+        let reserved = arena.capacity();
+        assert!(reserved >= 13);
+
+        // Shrinking releases the trailing empty slots, but their old keys stay
+        // stale once new slots are allocated in their place. The allocator
+        // decides how far the capacity actually drops.
+        arena.shrink_to_fit();
+        assert_eq!(arena.vacant_count(), 0);
+        assert!(arena.capacity() >= arena.len());
+        assert!(arena.capacity() < reserved);
+        for i in 5..10 {
+            arena.insert(i);
+        }
+        assert!(keys[5..].iter().all(|key| !arena.contains(*key)));
+        assert!(keys[..5].iter().all(|key| arena.contains(*key)));
+        assert_eq!(arena.len(), 10);
+    }
+
+    #[test]
+    fn stats() {
+        let mut arena = Arena::new();
+        arena.insert(-1);
+        assert!(arena.stats().is_none());
+
+        arena.enable_stats();
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        for key in &keys[..4] {
+            arena.remove(*key);
+        }
+        arena.insert(10);
+        arena.insert(11);
+
+        let stats = arena.stats().unwrap();
+        assert_eq!(stats.total_inserts, 12);
+        assert_eq!(stats.total_removes, 4);
+        assert_eq!(stats.reused_slots, 2);
+        assert_eq!(stats.peak_len, 11);
+        assert_eq!(stats.peak_slots, 11);
+        assert_eq!(stats.reuse_rate(), 2.0 / 12.0);
+
+        // The high-water marks survive clearing the arena.
+        arena.clear();
+        let stats = arena.stats().unwrap();
+        assert_eq!(stats.total_removes, 13);
+        assert_eq!(stats.peak_len, 11);
+    }
+
+    // A restored arena must hand out the same keys, and reuse free slots in
+    // the same order, as the arena it was saved from.
+    #[test]
+    fn encode_and_decode() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<String>> = (0..10).map(|i| arena.insert(i.to_string())).collect();
+        for key in [keys[7], keys[2], keys[4]] {
+            arena.remove(key);
+        }
+
+        // Keys saved alongside the arena are read back without being tied to
+        // an arena, so they work with the restored one.
+        let bytes = encoding::to_bytes(&(&arena, &keys));
+        let (mut restored, restored_keys): (Arena<String>, Vec<Key<String>>) =
+            encoding::from_bytes(&bytes).unwrap();
+        assert_eq!(restored_keys, keys);
+        assert_eq!(restored.len(), 7);
+        assert_eq!(restored.vacant_count(), 3);
+        for (key, restored_key) in keys.iter().zip(restored_keys) {
+            assert_eq!(restored.get(restored_key), arena.get(*key));
+        }
+        for i in 0..4 {
+            assert_eq!(restored.insert(i.to_string()), arena.insert(i.to_string()));
+        }
+
+        let empty: Arena<String> = Arena::new();
+        let restored: Arena<String> = encoding::from_bytes(&encoding::to_bytes(&empty)).unwrap();
+        assert!(restored.is_empty());
+    }
+
+    #[test]
+    fn decode_corrupted() {
+        let mut arena = Arena::new();
+        let key = arena.insert(1u8);
+        arena.insert(2u8);
+        arena.remove(key);
+        let bytes = encoding::to_bytes(&arena);
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert_eq!(encoding::from_bytes::<Arena<u8>>(&wrong_magic).err(),
+            Some(DecodeError::WrongFormat));
+
+        let mut future_version = bytes.clone();
+        future_version[4] = 99;
+        assert_eq!(encoding::from_bytes::<Arena<u8>>(&future_version).err(),
+            Some(DecodeError::UnsupportedVersion(99)));
+
+        // Point the free list at the occupied slot.
+        let mut bad_free_list = bytes.clone();
+        let last = bad_free_list.len() - 8;
+        bad_free_list[last] = 1;
+        assert!(matches!(encoding::from_bytes::<Arena<u8>>(&bad_free_list),
+            Err(DecodeError::InvalidData(_))));
+
+        assert_eq!(encoding::from_bytes::<Arena<u8>>(&bytes[..bytes.len() - 1]).err(),
+            Some(DecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn validate() {
+        let mut arena = Arena::new();
+        assert_eq!(arena.validate(), Ok(()));
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        arena.remove(keys[3]);
+        arena.retain(|_, value| *value % 2 == 0);
+        arena.insert(10);
+        assert_eq!(arena.validate(), Ok(()));
+
+        // Break the free list in every way validate() looks for.
+        arena.available.push(arena.storage.len());
+        arena.available.push(0);
+        let vacant = arena.available[0];
+        arena.available.push(vacant);
+        let forgotten = arena.available.remove(1);
+        assert_eq!(arena.validate(), Err(vec![
+            ArenaCorruption::FreeSlotOutOfBounds(10),
+            ArenaCorruption::FreeSlotOccupied(0),
+            ArenaCorruption::FreeSlotListedTwice(vacant),
+            ArenaCorruption::VacantSlotNotFree(forgotten)
+        ]));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "free slot 1 is occupied")]
+    fn validate_after_mutations() {
+        let mut arena = Arena::new();
+        arena.validate_after_mutations(true);
+        let key = arena.insert(0);
+        arena.insert(1);
+        arena.remove(key);
+        // Slip the occupied slot under the genuinely free one, so the next
+        // insert leaves it behind on the free list.
+        arena.available.insert(0, 1);
+        arena.insert(2);
+    }
+
+    #[test]
+    fn clear() {
+        let mut arena = Arena::new();
+        let keys: Vec<Key<i32>> = (0..10).map(|i| arena.insert(i)).collect();
+        arena.clear();
+        assert_eq!(arena.iter().count(), 0);
+        assert!(keys.iter().all(|h| arena.borrow(*h).is_none()));
+
+        // The cleared slots are reused in order.
+        for i in 0..10 {
+            assert_eq!(arena.insert(i).index(), i as usize);
+        }
+    }
+}
+
+// Identifies the arena that created a key. Only tracked in debug builds; in
+// release builds this is zero-sized and every check passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ArenaId {
+    // Zero means the key isn't tied to any particular arena.
+    #[cfg(debug_assertions)]
+    id: u64
+}
+
+impl ArenaId {
+    // An id that every arena accepts.
+    const UNBOUND: ArenaId = ArenaId {
+        #[cfg(debug_assertions)]
+        id: 0
+    };
+
+    // Returns an id that no other arena has.
+    fn unique() -> ArenaId {
+        #[cfg(debug_assertions)]
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        ArenaId {
+            #[cfg(debug_assertions)]
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        }
+    }
+
+    // Returns true if an arena with this id should accept a key with the
+    // other id.
+    fn accepts(&self, other: ArenaId) -> bool {
+        #[cfg(debug_assertions)]
+        {
+            other.id == 0 || other.id == self.id
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = other;
+            true
+        }
+    }
+}
+
+/// A reference to an element of type T stored in an [`Arena`].
+/// Pairs the index of a slot with the generation of that slot at the time the
+/// element was inserted. Once the element is removed, the slot's generation
+/// changes and the key goes stale: lookups through it return None instead
+/// of whatever is later stored in the same slot.
+/// In debug builds, a key also remembers which arena created it, and using it
+/// with any other arena panics.
+pub struct Key<T> {
+    index: usize,
+    generation: u64,
+    arena: ArenaId,
+    _element: PhantomData<fn() -> T>
+}
+
+impl<T> Key<T> {
+    fn new(index: usize, generation: u64, arena: ArenaId) -> Key<T> {
+        Key {
+            index,
+            generation,
+            arena,
+            _element: PhantomData
+        }
+    }
+
+    /// The index of the slot this key refers to.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The generation of the slot at the time this key was created.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Rebuild a key from its index and generation, e.g. after it has been
+    /// sent to a client and parsed back. The key is not checked against any
+    /// arena until it is used.
+    pub fn from_raw_parts(index: usize, generation: u64) -> Key<T> {
+        Key::new(index, generation, ArenaId::UNBOUND)
+    }
+}
+
+// A key is written as its index and generation. A decoded key isn't tied to
+// any particular arena.
+impl<T> Encode for Key<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.index.encode(out);
+        self.generation.encode(out);
+    }
+}
+
+impl<T> Decode for Key<T> {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Key::from_raw_parts(usize::decode(input)?, u64::decode(input)?))
+    }
+}
+
+// These are implemented by hand because deriving them would require T to
+// implement them too. The arena a key came from is deliberately left out of
+// comparisons, so a key rebuilt from its raw parts equals the original.
+impl<T> Clone for Key<T> {
+    fn clone(&self) -> Key<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Key<T> {}
+
+impl<T> PartialEq for Key<T> {
+    fn eq(&self, other: &Key<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Key<T> {}
+
+impl<T> Hash for Key<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key").
+            field("index", &self.index).
+            field("generation", &self.generation).
+            finish()
+    }
+}
+
+/// The reasons a key can fail to reach an element in an [`Arena`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaError {
+    /// The key's index was never allocated by the arena.
+    NeverAllocated,
+    /// The key's slot does not currently hold anything.
+    Vacant,
+    /// The key's element was removed and its slot now holds a newer one.
+    Stale
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::NeverAllocated =>
+                write!(f, "index out of bounds: the key's index was never allocated"),
+            ArenaError::Vacant =>
+                write!(f, "the key refers to an empty slot"),
+            ArenaError::Stale =>
+                write!(f, "the key is stale: its element was removed and the slot reused")
+        }
+    }
+}
+
+impl Error for ArenaError {}
+
+/// A broken internal invariant reported by Arena::validate(). Each variant
+/// holds the index of the slot involved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaCorruption {
+    /// The free list holds an index past the end of the arena's storage.
+    FreeSlotOutOfBounds(usize),
+    /// The free list holds the index of a slot that holds an element, which
+    /// would be overwritten by the next insert.
+    FreeSlotOccupied(usize),
+    /// The free list holds the same index more than once, so the slot would
+    /// be handed out twice.
+    FreeSlotListedTwice(usize),
+    /// An empty slot is missing from the free list and can never be reused.
+    VacantSlotNotFree(usize)
+}
+
+impl fmt::Display for ArenaCorruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaCorruption::FreeSlotOutOfBounds(index) =>
+                write!(f, "free slot {} is out of bounds", index),
+            ArenaCorruption::FreeSlotOccupied(index) =>
+                write!(f, "free slot {} is occupied", index),
+            ArenaCorruption::FreeSlotListedTwice(index) =>
+                write!(f, "free slot {} is listed twice", index),
+            ArenaCorruption::VacantSlotNotFree(index) =>
+                write!(f, "empty slot {} is missing from the free list", index)
+        }
+    }
+}
+
+impl Error for ArenaCorruption {}
+
+// A single storage slot. The generation is bumped every time the slot's data
+// is removed, so keys created before the removal no longer match.
+struct Slot<T> {
+    generation: u64,
+    data: Option<T>
+}
+
+/// Usage statistics collected by an [`Arena`] once enable_stats() is called.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArenaStats {
+    /// The number of elements inserted.
+    pub total_inserts: u64,
+    /// The number of elements removed, whether by remove(), retain(), drain()
+    /// or clear().
+    pub total_removes: u64,
+    /// The number of inserts that reused an empty slot instead of allocating
+    /// a new one.
+    pub reused_slots: u64,
+    /// The largest number of elements the arena has held at once.
+    pub peak_len: usize,
+    /// The largest number of slots (occupied or not) the arena has held.
+    pub peak_slots: usize
+}
+
+impl ArenaStats {
+    /// The fraction of inserts that reused an empty slot. Zero if nothing has
+    /// been inserted.
+    pub fn reuse_rate(&self) -> f64 {
+        if self.total_inserts == 0 {
+            0.0
+        }
+        else {
+            self.reused_slots as f64 / self.total_inserts as f64
+        }
     }
 }
 
 /// An "arena allocator." Provides convenient storage space to hold the nodes in
 /// linked data structures (e.g. graphs, trees) without running afoul of Rust's
 /// borrowing rules.
+/// Elements are referenced through the [`Key`] returned by insert().
 /// Can be indexed into using the [] operator.
 pub struct Arena<T> {
     // Indices that already exist in the vector but do not currently hold data.
     available: Vec<usize>,
     // Storage space maintained for the user of this struct.
-    storage: Vec<Option<T>>
+    storage: Vec<Slot<T>>,
+    // Ties the keys given out by this arena to it.
+    id: ArenaId,
+    // The generation given to newly allocated slots. Raised by compact() and
+    // shrink_to_fit() so that keys to released slots can't match new slots.
+    base_generation: u64,
+    // Only collected if the user asks for it.
+    stats: Option<ArenaStats>,
+    // Set by validate_after_mutations(). Ignored in release builds.
+    validate_on_mutation: bool
+}
+
+/// Translates the keys given out by an [`Arena`] before a call to compact()
+/// into the keys of the same elements afterwards.
+pub struct Remapping<T> {
+    // For each index before compaction: the generation the slot had and the
+    // index the slot's element was moved to, or None if the slot was empty.
+    old_slots: Vec<Option<(u64, usize)>>,
+    // The generation every element was given by the compaction.
+    generation: u64,
+    arena: ArenaId,
+    _element: PhantomData<fn() -> T>
+}
+
+impl<T> Remapping<T> {
+    /// Returns the new key of the element the old key referred to, or
+    /// None if the old key didn't refer to a live element.
+    pub fn get(&self, old: Key<T>) -> Option<Key<T>> {
+        match self.old_slots.get(old.index) {
+            Some(Some((generation, index))) if *generation == old.generation =>
+                Some(Key::new(*index, self.generation, self.arena)),
+            _ => None
+        }
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
 }
 
 impl<T> Arena<T> {
     /// Create an empty Arena.
     pub fn new() -> Arena<T> {
+        Arena::with_capacity(0)
+    }
+
+    /// Create an empty Arena with room for at least capacity elements.
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
         Arena {
             available: Vec::new(),
-            storage: Vec::new()
+            storage: Vec::with_capacity(capacity),
+            id: ArenaId::unique(),
+            base_generation: 0,
+            stats: None,
+            validate_on_mutation: false
         }
     }
 
-    /// Add an element to the arena and return its index, which will be used to
-    /// reference that element going foward.
-    pub fn insert(&mut self, data: T) -> usize {
+    /// The number of elements stored in the arena.
+    pub fn len(&self) -> usize {
+        self.storage.len() - self.available.len()
+    }
+
+    /// Returns true if the arena holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of elements the arena can hold without allocating more
+    /// memory.
+    pub fn capacity(&self) -> usize {
+        self.storage.capacity()
+    }
+
+    /// The number of allocated slots that don't currently hold anything.
+    pub fn vacant_count(&self) -> usize {
+        self.available.len()
+    }
+
+    /// Make sure at least additional more elements can be inserted without
+    /// allocating. Empty slots count towards the reservation.
+    pub fn reserve(&mut self, additional: usize) {
+        let new_slots = additional.saturating_sub(self.available.len());
+        let unused = self.storage.capacity() - self.storage.len();
+        if new_slots > unused {
+            self.storage.reserve(new_slots);
+        }
+    }
+
+    /// Release the empty slots at the end of the arena and as much unused
+    /// memory as possible. Unlike compact(), no element is moved, so every
+    /// key stays valid.
+    pub fn shrink_to_fit(&mut self) {
+        let old_len = self.storage.len();
+        while let Some(slot) = self.storage.last() {
+            if slot.data.is_some() {
+                break;
+            }
+            // New slots allocated at this index must not revive keys to the
+            // released one.
+            self.base_generation = self.base_generation.max(slot.generation);
+            self.storage.pop();
+        }
+
+        if self.storage.len() < old_len {
+            let len = self.storage.len();
+            self.available.retain(|index| *index < len);
+        }
+        self.storage.shrink_to_fit();
+        self.available.shrink_to_fit();
+        self.check_invariants();
+    }
+
+    /// Start collecting usage statistics. Does nothing if they are already
+    /// being collected.
+    pub fn enable_stats(&mut self) {
+        if self.stats.is_none() {
+            self.stats = Some(ArenaStats {
+                peak_len: self.len(),
+                peak_slots: self.storage.len(),
+                ..ArenaStats::default()
+            });
+        }
+    }
+
+    /// The usage statistics collected since enable_stats() was called, or
+    /// None if it hasn't been.
+    pub fn stats(&self) -> Option<&ArenaStats> {
+        self.stats.as_ref()
+    }
+
+    /// Add an element to the arena and return a key to it, which will be
+    /// used to reference that element going foward.
+    pub fn insert(&mut self, data: T) -> Key<T> {
         let index;
+        let reused;
         // If we already have an empty slot, use it. Otherwise, add a new one.
         if let Some(i) = self.available.pop() {
-            let _ = self.storage[i].insert(data);
+            let _ = self.storage[i].data.insert(data);
             index = i;
+            reused = true;
         }
         else {
-            self.storage.push(Some(data));
+            self.storage.push(Slot { generation: self.base_generation, data: Some(data) });
             index = self.storage.len() - 1;
+            reused = false;
+        }
+
+        let (len, slots) = (self.len(), self.storage.len());
+        if let Some(stats) = &mut self.stats {
+            stats.total_inserts += 1;
+            if reused {
+                stats.reused_slots += 1;
+            }
+            stats.peak_len = stats.peak_len.max(len);
+            stats.peak_slots = stats.peak_slots.max(slots);
         }
-        index
+
+        self.check_invariants();
+        Key::new(index, self.storage[index].generation, self.id)
     }
 
-    /// Remove and return the item at a given index or None if that index is
-    /// allocated but empty.
+    /// Remove and return the item referenced by a key. Returns None if the
+    /// slot is empty or the key is stale (its item was already removed).
     /// # Panics
     /// Panic if the index was never allocated (index out of bounds).
-    pub fn remove(&mut self, index: usize) -> Option<T> {
-        // Mark the emptied slot as available.
-        if let Some(item) = self.storage[index].take() {
-            self.available.push(index);
-            Some(item)
+    pub fn remove(&mut self, key: Key<T>) -> Option<T> {
+        match self.try_remove(key) {
+            Ok(item) => Some(item),
+            Err(ArenaError::NeverAllocated) => panic!("{}", ArenaError::NeverAllocated),
+            Err(_) => None
         }
-        else {
-            None
+    }
+
+    /// Remove and return the item referenced by a key, or report why the
+    /// key doesn't reach an item.
+    pub fn try_remove(&mut self, key: Key<T>) -> Result<T, ArenaError> {
+        self.check(key)?;
+        self.available.push(key.index);
+        // check() has made sure the slot holds this key's item.
+        let item = self.vacate(key.index).unwrap();
+        self.check_invariants();
+        Ok(item)
+    }
+
+    /// Returns true if the key refers to an element currently stored in
+    /// the arena.
+    pub fn contains(&self, key: Key<T>) -> bool {
+        self.check(key).is_ok()
+    }
+
+    /// Returns a reference to the value referenced by the given key, or
+    /// None if the key doesn't reach a value for any reason.
+    pub fn get(&self, key: Key<T>) -> Option<&T> {
+        self.check(key).ok()?;
+        self.storage[key.index].data.as_ref()
+    }
+
+    /// Returns a mutable reference to the value referenced by the given
+    /// key, or None if the key doesn't reach a value for any reason.
+    pub fn get_mut(&mut self, key: Key<T>) -> Option<&mut T> {
+        self.check(key).ok()?;
+        self.storage[key.index].data.as_mut()
+    }
+
+    // Make sure the key refers to an element that is currently stored in
+    // the arena. If not, say why.
+    fn check(&self, key: Key<T>) -> Result<(), ArenaError> {
+        debug_assert!(self.id.accepts(key.arena),
+            "The key was created by a different arena.");
+        match self.storage.get(key.index) {
+            None => Err(ArenaError::NeverAllocated),
+            Some(slot) if slot.data.is_none() => Err(ArenaError::Vacant),
+            Some(slot) if slot.generation != key.generation => Err(ArenaError::Stale),
+            Some(_) => Ok(())
         }
+    }
 
+    // Take the data out of the slot at index, if there is any, and bump the
+    // slot's generation to invalidate every key that refers to it.
+    // The caller is responsible for marking the slot as available.
+    fn vacate(&mut self, index: usize) -> Option<T> {
+        let slot = &mut self.storage[index];
+        let item = slot.data.take();
+        if item.is_some() {
+            slot.generation = slot.generation.wrapping_add(1);
+            if let Some(stats) = &mut self.stats {
+                stats.total_removes += 1;
+            }
+        }
+        item
     }
 
-    /// Returns a mutable reference to the value at the given index.
-    /// Returns None if the index was allocated but does not currently hold
-    /// anything.
+    /// Returns a reference to the value referenced by the given key.
+    /// Returns None if the slot does not currently hold anything or the key
+    /// is stale.
     /// # Panics
     /// Panics if the index is out of bounds.
-    pub fn borrow_mutable(&mut self, index: usize) -> Option<&mut T> {
-        match self.storage.get_mut(index) {
-            Some(reference) => reference.as_mut(),
-            None => { panic!("Index out of bounds. Cannot borrow.") }
+    pub fn borrow(&self, key: Key<T>) -> Option<&T> {
+        match self.check(key) {
+            Err(ArenaError::NeverAllocated) => panic!("{}", ArenaError::NeverAllocated),
+            _ => self.get(key)
+        }
+    }
+
+    /// Returns a mutable reference to the value referenced by the given key.
+    /// Returns None if the slot does not currently hold anything or the key
+    /// is stale.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    pub fn borrow_mutable(&mut self, key: Key<T>) -> Option<&mut T> {
+        match self.check(key) {
+            Err(ArenaError::NeverAllocated) => panic!("{}", ArenaError::NeverAllocated),
+            _ => self.get_mut(key)
+        }
+    }
+
+    /// Get an iterator over the elements in the arena and their keys.
+    /// Empty slots are skipped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            slots: self.storage.iter().enumerate(),
+            arena: self.id
+        }
+    }
+
+    /// Get an iterator over mutable references to the elements in the arena
+    /// and their keys. Empty slots are skipped.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            slots: self.storage.iter_mut().enumerate(),
+            arena: self.id
+        }
+    }
+
+    /// Get an iterator over the keys of every element in the arena.
+    pub fn indices(&self) -> Indices<'_, T> {
+        Indices {
+            iter: self.iter()
+        }
+    }
+
+    /// Remove every element from the arena, returning them (and the keys
+    /// they were stored under) through an iterator. If the iterator is dropped
+    /// before it is exhausted, the remaining elements are dropped with it.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain {
+            arena: self,
+            next: 0
+        }
+    }
+
+    /// Keep only the elements for which f returns true. Every other element
+    /// is removed and its slot becomes available for reuse.
+    pub fn retain<F>(&mut self, mut f: F)
+        where F: FnMut(Key<T>, &mut T) -> bool
+    {
+        for index in 0..self.storage.len() {
+            let slot = &mut self.storage[index];
+            let key = Key::new(index, slot.generation, self.id);
+            let keep = match slot.data.as_mut() {
+                Some(data) => f(key, data),
+                None => true
+            };
+
+            if !keep {
+                self.vacate(index);
+                self.available.push(index);
+            }
+        }
+        self.check_invariants();
+    }
+
+    /// Move every element to the front of the arena, in order, and release
+    /// the memory used by the empty slots.
+    /// Every key given out before the compaction becomes stale. The
+    /// returned table translates them into keys that work afterwards.
+    pub fn compact(&mut self) -> Remapping<T> {
+        self.compact_with(|_, _| {})
+    }
+
+    /// Like compact(), but calls f on every element once the elements have
+    /// been moved, so that keys stored inside the elements (e.g. links to
+    /// child nodes) can be rewritten in the same pass.
+    pub fn compact_with<F>(&mut self, mut f: F) -> Remapping<T>
+        where F: FnMut(&mut T, &Remapping<T>)
+    {
+        // Give every element a generation no key has seen yet, so the old
+        // keys can't accidentally refer to the new slots.
+        let generation = self.storage.iter().
+            map(|slot| slot.generation).
+            max().
+            unwrap_or(self.base_generation).
+            max(self.base_generation).
+            wrapping_add(1);
+
+        let mut old_slots = Vec::with_capacity(self.storage.len());
+        let mut compacted = Vec::with_capacity(self.storage.len() - self.available.len());
+        for slot in self.storage.drain(..) {
+            match slot.data {
+                Some(data) => {
+                    old_slots.push(Some((slot.generation, compacted.len())));
+                    compacted.push(Slot { generation, data: Some(data) });
+                },
+                None => old_slots.push(None)
+            }
+        }
+
+        self.storage = compacted;
+        self.available = Vec::new();
+        self.base_generation = generation;
+
+        let remapping = Remapping {
+            old_slots,
+            generation,
+            arena: self.id,
+            _element: PhantomData
+        };
+        for slot in self.storage.iter_mut() {
+            // Every slot holds data right after compaction.
+            f(slot.data.as_mut().unwrap(), &remapping);
         }
+        self.check_invariants();
+        remapping
     }
+
+    /// Remove every element from the arena. The allocated slots are kept and
+    /// will be reused, starting from the lowest index.
+    pub fn clear(&mut self) {
+        for index in 0..self.storage.len() {
+            self.vacate(index);
+        }
+        // Every slot is now empty. Rebuild the free list so that the lowest
+        // indices are handed out first.
+        self.available = (0..self.storage.len()).rev().collect();
+        self.check_invariants();
+    }
+
+    /// Check the arena's internal bookkeeping: every index on the free list
+    /// must be in bounds, refer to an empty slot and be listed only once, and
+    /// every empty slot must be on the free list. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<ArenaCorruption>> {
+        let mut problems = Vec::new();
+        let mut listed = vec![false; self.storage.len()];
+        for index in &self.available {
+            match self.storage.get(*index) {
+                None => problems.push(ArenaCorruption::FreeSlotOutOfBounds(*index)),
+                Some(slot) if slot.data.is_some() =>
+                    problems.push(ArenaCorruption::FreeSlotOccupied(*index)),
+                Some(_) if listed[*index] =>
+                    problems.push(ArenaCorruption::FreeSlotListedTwice(*index)),
+                Some(_) => listed[*index] = true
+            }
+        }
+        for (index, slot) in self.storage.iter().enumerate() {
+            if slot.data.is_none() && !listed[index] {
+                problems.push(ArenaCorruption::VacantSlotNotFree(index));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(problems)
+        }
+    }
+
+    /// In debug builds, run validate() after every change to the arena's
+    /// layout (inserts, removals, compaction, etc.) and panic if it fails.
+    /// Each check takes O(n) time. Does nothing in release builds.
+    pub fn validate_after_mutations(&mut self, enabled: bool) {
+        self.validate_on_mutation = enabled;
+    }
+
+    // Panic with validate()'s report if validate_after_mutations() is on.
+    fn check_invariants(&self) {
+        if cfg!(debug_assertions) && self.validate_on_mutation {
+            if let Err(problems) = self.validate() {
+                let report: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                panic!("The arena was corrupted by a mutation: {}. This is a bug in Arena.",
+                    report.join("; "));
+            }
+        }
+    }
+}
+
+/// Iterator over the elements of an [`Arena`]. Created by Arena::iter().
+pub struct Iter<'a, T> {
+    slots: std::iter::Enumerate<std::slice::Iter<'a, Slot<T>>>,
+    arena: ArenaId
 }
 
-/// Returns the stored value at the given index. Returns None if the index was
-/// allocated but does not currently hold anything.
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Key<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, slot) in self.slots.by_ref() {
+            if let Some(data) = &slot.data {
+                return Some((Key::new(index, slot.generation, self.arena), data));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over mutable references to the elements of an [`Arena`].
+/// Created by Arena::iter_mut().
+pub struct IterMut<'a, T> {
+    slots: std::iter::Enumerate<std::slice::IterMut<'a, Slot<T>>>,
+    arena: ArenaId
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Key<T>, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, slot) in self.slots.by_ref() {
+            if let Some(data) = &mut slot.data {
+                return Some((Key::new(index, slot.generation, self.arena), data));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the keys of the elements of an [`Arena`].
+/// Created by Arena::indices().
+pub struct Indices<'a, T> {
+    iter: Iter<'a, T>
+}
+
+impl<'a, T> Iterator for Indices<'a, T> {
+    type Item = Key<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, _)| key)
+    }
+}
+
+/// Iterator that removes the elements of an [`Arena`].
+/// Created by Arena::drain().
+pub struct Drain<'a, T> {
+    arena: &'a mut Arena<T>,
+    // The index of the next slot to look at.
+    next: usize
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = (Key<T>, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.arena.storage.len() {
+            let index = self.next;
+            self.next += 1;
+            let generation = self.arena.storage[index].generation;
+            if let Some(data) = self.arena.vacate(index) {
+                self.arena.available.push(index);
+                return Some((Key::new(index, generation, self.arena.id), data));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, T> Drop for Drain<'a, T> {
+    fn drop(&mut self) {
+        // Remove (and drop) whatever the caller didn't consume.
+        for _ in self.by_ref() {}
+        self.arena.check_invariants();
+    }
+}
+
+// Identifies an encoded Arena, followed by the format version.
+const ARENA_MAGIC: &[u8; 4] = b"ARNA";
+const ARENA_FORMAT_VERSION: u8 = 1;
+
+/// Writes the arena in a versioned binary format that records every slot
+/// (including empty ones, with their generations) and the free list, so that
+/// a decoded arena accepts exactly the same keys as the original and reuses
+/// its empty slots in the same order.
+/// Statistics are not saved.
+// Version 1 layout:
+//  "ARNA", version: u8, base generation: u64,
+//  slot count: u64, then for each slot: generation: u64, data: Option<T>,
+//  free list length: u64, then each free index: u64.
+impl<T> Encode for Arena<T>
+    where T: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        encoding::encode_header(out, ARENA_MAGIC, ARENA_FORMAT_VERSION);
+        self.base_generation.encode(out);
+        self.storage.len().encode(out);
+        for slot in &self.storage {
+            slot.generation.encode(out);
+            slot.data.encode(out);
+        }
+        self.available.encode(out);
+    }
+}
+
+/// Reads an arena written by its Encode implementation. The free list is
+/// checked against the slots, so a corrupted checkpoint is reported instead of
+/// producing an arena that hands out occupied slots.
+impl<T> Decode for Arena<T>
+    where T: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        encoding::decode_header(input, ARENA_MAGIC, ARENA_FORMAT_VERSION)?;
+        let base_generation = u64::decode(input)?;
+        let len = usize::decode(input)?;
+        let mut storage = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            let generation = u64::decode(input)?;
+            let data = Option::<T>::decode(input)?;
+            storage.push(Slot { generation, data });
+        }
+        let available = Vec::<usize>::decode(input)?;
+
+        let mut arena = Arena::new();
+        arena.available = available;
+        arena.storage = storage;
+        arena.base_generation = base_generation;
+        // Every empty slot must be on the free list exactly once, and nothing
+        // else may be.
+        if let Err(problems) = arena.validate() {
+            return Err(DecodeError::InvalidData(problems[0].to_string()));
+        }
+        Ok(arena)
+    }
+}
+
+/// Returns the value referenced by the given key.
+/// # Panics
+/// Panic if the index was never allocated (index out of bounds) or if the
+/// key is stale or its slot is empty. Use get() to check instead.
+impl<T> ops::Index<Key<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, key: Key<T>) -> &Self::Output {
+        if let Err(e) = self.check(key) {
+            panic!("{}", e);
+        }
+        // check() has made sure the slot holds this key's item.
+        self.storage[key.index].data.as_ref().unwrap()
+    }
+}
+
+/// Returns a mutable reference to the value referenced by the given key,
+/// allowing it to be replaced with arena[key] = value.
 /// # Panics
-/// Panic if the index was never allocated (index out of bounds).
-impl<T> ops::Index<usize> for Arena<T> {
-    type Output = Option<T>;
+/// Panic if the index was never allocated (index out of bounds) or if the
+/// key is stale or its slot is empty. Use get_mut() to check instead.
+impl<T> ops::IndexMut<Key<T>> for Arena<T> {
+    fn index_mut(&mut self, key: Key<T>) -> &mut Self::Output {
+        if let Err(e) = self.check(key) {
+            panic!("{}", e);
+        }
+        // check() has made sure the slot holds this key's item.
+        self.storage[key.index].data.as_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod secondary_map_tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_get() {
+        let mut arena = Arena::new();
+        let mut colors = SecondaryMap::new();
+        let keys: Vec<Key<&str>> = ["a", "b", "c"].into_iter().map(|s| arena.insert(s)).collect();
+
+        assert!(colors.is_empty());
+        assert_eq!(colors.insert(keys[0], "red"), None);
+        assert_eq!(colors.insert(keys[2], "blue"), None);
+        assert_eq!(colors.insert(keys[2], "green"), Some("blue"));
+        assert_eq!(colors.len(), 2);
+
+        assert_eq!(colors.get(keys[0]), Some(&"red"));
+        assert_eq!(colors.get(keys[1]), None);
+        assert_eq!(colors[keys[2]], "green");
+        *colors.get_mut(keys[0]).unwrap() = "black";
+        assert_eq!(colors.iter().map(|(k, c)| (arena[k], *c)).collect::<Vec<_>>(),
+            vec![("a", "black"), ("c", "green")]);
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.storage[index]
+        assert_eq!(colors.remove(keys[0]), Some("black"));
+        assert!(!colors.contains_key(keys[0]));
+        assert_eq!(colors.len(), 1);
+    }
+
+    // An entry must not be visible through the key of an element that later
+    // reuses the same arena slot.
+    #[test]
+    fn reused_slot() {
+        let mut arena = Arena::new();
+        let mut distances = SecondaryMap::new();
+        let old = arena.insert(0);
+        distances.insert(old, 10);
+        arena.remove(old);
+        let new = arena.insert(1);
+
+        assert_eq!(old.index(), new.index());
+        assert!(distances.get(new).is_none());
+        assert_eq!(distances.insert(new, 20), None);
+        assert!(distances.get(old).is_none());
+        assert_eq!(distances.len(), 1);
+    }
+}
+
+/// Stores extra data of type V alongside the elements of an [`Arena<T>`],
+/// using the same [`Key<T>`]s. Lets an algorithm attach its own per-element
+/// data (e.g. colors or distances in a graph search) without changing T.
+/// An entry only answers to the exact key it was inserted with, so once an
+/// arena element is removed and its slot reused, the new element's key won't
+/// see the old element's entry.
+pub struct SecondaryMap<T, V> {
+    // Indexed like the arena's storage. Each entry remembers the generation
+    // of the key it was inserted with.
+    slots: Vec<Option<(u64, V)>>,
+    len: usize,
+    _element: PhantomData<fn() -> T>
+}
+
+impl<T, V> Default for SecondaryMap<T, V> {
+    fn default() -> SecondaryMap<T, V> {
+        SecondaryMap::new()
+    }
+}
+
+impl<T, V> SecondaryMap<T, V> {
+    /// Create an empty SecondaryMap.
+    pub fn new() -> SecondaryMap<T, V> {
+        SecondaryMap {
+            slots: Vec::new(),
+            len: 0,
+            _element: PhantomData
+        }
+    }
+
+    /// The number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Associate a value with a key and return the value previously
+    /// associated with the same key, if there was one. An entry left behind
+    /// by an older element in the same slot is replaced and not returned.
+    pub fn insert(&mut self, key: Key<T>, value: V) -> Option<V> {
+        if key.index >= self.slots.len() {
+            self.slots.resize_with(key.index + 1, || None);
+        }
+
+        match self.slots[key.index].replace((key.generation, value)) {
+            Some((generation, old)) if generation == key.generation => Some(old),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns a reference to the value associated with the key, if any.
+    pub fn get(&self, key: Key<T>) -> Option<&V> {
+        match self.slots.get(key.index) {
+            Some(Some((generation, value))) if *generation == key.generation => Some(value),
+            _ => None
+        }
+    }
+
+    /// Returns a mutable reference to the value associated with the key, if
+    /// any.
+    pub fn get_mut(&mut self, key: Key<T>) -> Option<&mut V> {
+        match self.slots.get_mut(key.index) {
+            Some(Some((generation, value))) if *generation == key.generation => Some(value),
+            _ => None
+        }
+    }
+
+    /// Returns true if a value is associated with the key.
+    pub fn contains_key(&self, key: Key<T>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove and return the value associated with the key, if any.
+    pub fn remove(&mut self, key: Key<T>) -> Option<V> {
+        if !self.contains_key(key) {
+            return None;
+        }
+        self.len -= 1;
+        // contains_key() has made sure there is an entry to take.
+        self.slots[key.index].take().map(|(_, value)| value)
+    }
+
+    /// Remove every entry from the map.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    /// Get an iterator over the keys and values in the map, in key index
+    /// order. The keys aren't tied to any particular arena.
+    pub fn iter(&self) -> impl Iterator<Item = (Key<T>, &V)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, value)|
+                (Key::from_raw_parts(index, *generation), value))
+        })
+    }
+}
+
+/// Returns the value associated with the given key.
+/// # Panics
+/// Panics if no value is associated with the key. Use get() to check instead.
+impl<T, V> ops::Index<Key<T>> for SecondaryMap<T, V> {
+    type Output = V;
+
+    fn index(&self, key: Key<T>) -> &Self::Output {
+        match self.get(key) {
+            Some(value) => value,
+            None => { panic!("No value is associated with the key.") }
+        }
+    }
+}
+
+#[cfg(test)]
+mod concurrent_arena_tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread;
+
+    #[test]
+    fn insert_and_get() {
+        let arena = ConcurrentArena::new();
+        let keys: Vec<Key<i32>> = (0..1000).map(|i| arena.insert(i)).collect();
+        assert_eq!(arena.len(), 1000);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(arena.get(*key), Some(&(i as i32)));
+            assert_eq!(arena[*key], i as i32);
+        }
+        assert!(arena.get(Key::from_raw_parts(1000, 0)).is_none());
+        assert!(arena.get(Key::from_raw_parts(usize::MAX, 0)).is_none());
+        assert!(arena.get(Key::from_raw_parts(0, 1)).is_none());
+    }
+
+    // Indices past the last bucket can't be looked up, and must not be
+    // mistaken for indices into it.
+    #[test]
+    fn get_past_last_bucket() {
+        let arena = ConcurrentArena::new();
+        arena.insert(0);
+        for index in [usize::MAX - 1, usize::MAX - FIRST_BUCKET_SIZE, usize::MAX / 2 + 1] {
+            assert!(arena.get(Key::from_raw_parts(index, 0)).is_none());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The key doesn't refer to an element in this ConcurrentArena.")]
+    fn index_past_last_bucket() {
+        let arena = ConcurrentArena::new();
+        arena.insert(0);
+        let _ = arena[Key::from_raw_parts(usize::MAX - 1, 0)];
+    }
+
+    // Many threads inserting at once must each get their own slot: no slot is
+    // lost and none is handed out twice.
+    #[test]
+    fn concurrent_inserts() {
+        let threads = 8;
+        let per_thread = 10_000;
+        let arena = ConcurrentArena::new();
+
+        let keys = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|t| {
+                let arena = &arena;
+                scope.spawn(move || {
+                    (0..per_thread).
+                        map(|i| (t, i, arena.insert((t, i)))).
+                        collect::<Vec<_>>()
+                })
+            }).collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect::<Vec<_>>()
+        });
+
+        assert_eq!(keys.len(), threads * per_thread);
+        assert_eq!(arena.len(), threads * per_thread);
+        let indices: HashSet<usize> = keys.iter().map(|(_, _, key)| key.index()).collect();
+        assert_eq!(indices.len(), threads * per_thread);
+        assert!(indices.iter().all(|i| *i < threads * per_thread));
+        for (t, i, key) in &keys {
+            assert_eq!(arena[*key], (*t, *i));
+        }
+
+        // The keys keep working once the arena is handed over to a regular
+        // Arena.
+        let arena = arena.into_arena();
+        assert_eq!(arena.iter().count(), threads * per_thread);
+        for (t, i, key) in keys {
+            assert_eq!(arena[key], (t, i));
+        }
+    }
+
+    // Readers should see every published entry while writers keep adding more.
+    #[test]
+    fn read_while_inserting() {
+        let arena = ConcurrentArena::new();
+        let first = arena.insert(0);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..5_000 {
+                        let key = arena.insert(i);
+                        assert_eq!(arena[key], i);
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for _ in 0..1_000 {
+                    assert_eq!(arena[first], 0);
+                    // Entries are either published with their value or not
+                    // visible at all.
+                    let seen = arena.iter().count();
+                    assert!((1..=20_001).contains(&seen));
+                }
+            });
+        });
+
+        assert_eq!(arena.iter().count(), 20_001);
+    }
+}
+
+// The size of the first bucket in a ConcurrentArena. Every bucket after it is
+// twice the size of the one before.
+const FIRST_BUCKET_SIZE: usize = 32;
+// Enough buckets to cover every possible index.
+const BUCKETS: usize = (usize::BITS - FIRST_BUCKET_SIZE.trailing_zeros()) as usize;
+
+/// An arena that can be filled from several threads at once, e.g. to build a
+/// large tree or graph in parallel.
+/// Inserting only needs a shared reference, and the keys it returns stay
+/// valid for the life of the arena. Reading an element that has been inserted
+/// never takes a lock.
+/// Elements can't be removed while the arena is shared. Once construction is
+/// finished, into_arena() turns it into a regular [`Arena`] that accepts the
+/// same keys.
+// Slots live in buckets that double in size and are never moved once
+// allocated, so references to published elements stay valid while other
+// threads keep inserting. Each insert claims a unique index with a single
+// atomic increment.
+pub struct ConcurrentArena<T> {
+    buckets: [OnceLock<Box<[OnceLock<T>]>>; BUCKETS],
+    // The next index to hand out.
+    next: AtomicUsize,
+    id: ArenaId
+}
+
+impl<T> Default for ConcurrentArena<T> {
+    fn default() -> ConcurrentArena<T> {
+        ConcurrentArena::new()
+    }
+}
+
+impl<T> ConcurrentArena<T> {
+    /// Create an empty ConcurrentArena.
+    pub fn new() -> ConcurrentArena<T> {
+        ConcurrentArena {
+            buckets: [const { OnceLock::new() }; BUCKETS],
+            next: AtomicUsize::new(0),
+            id: ArenaId::unique()
+        }
+    }
+
+    // Returns the bucket holding the given index and the index's position in
+    // that bucket.
+    fn locate(index: usize) -> (usize, usize) {
+        // Bucket b starts at FIRST_BUCKET_SIZE * (2^b - 1).
+        let scaled = index / FIRST_BUCKET_SIZE + 1;
+        let bucket = (usize::BITS - 1 - scaled.leading_zeros()) as usize;
+        let start = FIRST_BUCKET_SIZE * ((1 << bucket) - 1);
+        (bucket, index - start)
+    }
+
+    /// Add an element to the arena and return a key to it.
+    /// # Panics
+    /// Panics if the arena has run out of indices.
+    pub fn insert(&self, data: T) -> Key<T> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        let (bucket, offset) = ConcurrentArena::<T>::locate(index);
+        // The last few indices fall past the final bucket.
+        if bucket >= BUCKETS {
+            panic!("ConcurrentArena has run out of indices.");
+        }
+
+        let slots = self.buckets[bucket].get_or_init(|| {
+            (0..(FIRST_BUCKET_SIZE << bucket)).map(|_| OnceLock::new()).collect()
+        });
+        if slots[offset].set(data).is_err() {
+            panic!("A ConcurrentArena slot was handed out twice. \
+                This is a bug in ConcurrentArena.");
+        }
+
+        Key::new(index, 0, self.id)
+    }
+
+    /// Returns a reference to the element referenced by the given key, or
+    /// None if the key doesn't refer to an element in this arena.
+    pub fn get(&self, key: Key<T>) -> Option<&T> {
+        debug_assert!(self.id.accepts(key.arena),
+            "The key was created by a different arena.");
+        // Elements are never removed, so every slot stays in its first
+        // generation.
+        if key.generation != 0 {
+            return None;
+        }
+
+        // The last few indices fall past the final bucket, so no key can
+        // refer to them.
+        let (bucket, offset) = ConcurrentArena::<T>::locate(key.index);
+        self.buckets.get(bucket)?.get()?.get(offset)?.get()
+    }
+
+    /// The number of elements that have been inserted, including inserts that
+    /// other threads have started but not yet finished.
+    pub fn len(&self) -> usize {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get an iterator over the elements that have been inserted so far and
+    /// their keys. Elements inserted while iterating may or may not be seen.
+    pub fn iter(&self) -> impl Iterator<Item = (Key<T>, &T)> {
+        let len = self.len();
+        (0..len).filter_map(move |index| {
+            let key = Key::new(index, 0, self.id);
+            self.get(key).map(|data| (key, data))
+        })
+    }
+
+    /// Turn the arena into a regular [`Arena`] once construction is finished.
+    /// Every key given out by this arena keeps working with the new one.
+    pub fn into_arena(self) -> Arena<T> {
+        let len = self.next.into_inner();
+        let mut storage = Vec::with_capacity(len);
+        for bucket in self.buckets {
+            let Some(slots) = bucket.into_inner() else { break };
+            for slot in slots.into_vec() {
+                if storage.len() == len {
+                    break;
+                }
+                storage.push(Slot { generation: 0, data: slot.into_inner() });
+            }
+        }
+
+        // A slot can only be empty if the thread inserting into it panicked.
+        // Bump its generation so that it behaves like any other freed slot.
+        let mut available = Vec::new();
+        for (index, slot) in storage.iter_mut().enumerate().rev() {
+            if slot.data.is_none() {
+                slot.generation = 1;
+                available.push(index);
+            }
+        }
+
+        Arena {
+            available,
+            storage,
+            id: self.id,
+            base_generation: 0,
+            stats: None,
+            validate_on_mutation: false
+        }
+    }
+}
+
+/// Returns the element referenced by the given key.
+/// # Panics
+/// Panics if the key doesn't refer to an element in this arena. Use get() to
+/// check instead.
+impl<T> ops::Index<Key<T>> for ConcurrentArena<T> {
+    type Output = T;
+
+    fn index(&self, key: Key<T>) -> &Self::Output {
+        match self.get(key) {
+            Some(data) => data,
+            None => { panic!("The key doesn't refer to an element in this ConcurrentArena.") }
+        }
     }
 }
@@ -1,9 +1,15 @@
-use std::rc::{Rc, Weak};
+use crate::arena::{Arena, Key};
+use std::alloc::{self, Layout};
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut, Index, IndexMut, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo,
+    RangeToInclusive};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+use std::slice;
 
-/* TODO: Rust doesn't allow dynamic sizing of its standard arrays.
- * Apparently, implementing a dynamic array requires "advanced" Rust,
- * including unsafe code (https://doc.rust-lang.org/nomicon/vec/vec.html).
- * This doesn't seem like a good place to start.
 #[cfg(test)]
 mod dynamic_array_tests {
     use super::*;
@@ -12,6 +18,18 @@ mod dynamic_array_tests {
     // Make sure the size and count are updated correctly when items are added.
     #[test]
     fn add_items() {
+        let mut array = DynamicArray::new();
+        assert!(array.is_empty());
+        for i in 0..100 {
+            array.push(i);
+            assert_eq!(array.len(), i + 1);
+            assert!(array.capacity() >= array.len());
+        }
+        // Doubling from 1 gives the next power of two.
+        assert_eq!(array.capacity(), 128);
+        assert_eq!(array[42], 42);
+        assert_eq!(&array[..3], &[0, 1, 2]);
+        assert_eq!(array.iter().sum::<usize>(), 4950);
     }
 
     // Make sure that attempting to index into unused but allocated space
@@ -19,27 +37,401 @@ mod dynamic_array_tests {
     #[test]
     #[should_panic(expected = "Attempted to access unitialized dynamic array index.")]
     fn bounds_check() {
+        let mut array = DynamicArray::with_capacity(4);
+        array.push(1);
+        let _ = array[1];
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut array: DynamicArray<String> = ["b", "d"].iter().map(|s| s.to_string()).collect();
+        array.insert(0, String::from("a"));
+        array.insert(2, String::from("c"));
+        array.insert(4, String::from("e"));
+        assert_eq!(array.join(""), "abcde");
+
+        assert_eq!(array.remove(1), "b");
+        assert_eq!(array.remove(3), "e");
+        array[0].push('!');
+        assert_eq!(array.join(""), "a!cd");
+        assert_eq!(array.pop().as_deref(), Some("d"));
+        assert_eq!(array.len(), 2);
+    }
+
+    #[test]
+    fn growth_and_shrinking() {
+        let mut array = DynamicArray::with_growth_factor(1.5);
+        array.extend(0..10);
+        assert_eq!(array.capacity(), 12);
+
+        // The array shrinks once it is less than 1 / 1.5² full.
+        while array.len() > 6 {
+            array.pop();
+        }
+        assert_eq!(array.capacity(), 12);
+        array.pop();
+        assert_eq!(array.capacity(), 8);
+        while array.pop().is_some() {}
+        assert_eq!(array.capacity(), 1);
+        array.push(1);
+        assert_eq!(array[0], 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let mut array = DynamicArray::new();
+        for _ in 0..1000 {
+            array.push(());
+        }
+        array.insert(500, ());
+        assert_eq!(array.remove(0), ());
+        assert_eq!(array.len(), 1000);
+        assert_eq!(array.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn drops_items() {
+        let item = Rc::new(0);
+        let mut array = DynamicArray::new();
+        for _ in 0..10 {
+            array.push(Rc::clone(&item));
+        }
+        array.remove(3);
+        array.pop();
+        assert_eq!(Rc::strong_count(&item), 9);
+        drop(array);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
+
+// An allocation with room for capacity values of type T, which it doesn't
+// keep track of or drop. DynamicArray decides which slots are in use.
+// Zero-sized types never allocate and have unlimited capacity.
+struct RawBuf<T> {
+    ptr: NonNull<T>,
+    capacity: usize,
+    _owns: PhantomData<T>
+}
+
+// RawBuf owns the values in it just like a Box<[T]> would.
+unsafe impl<T> Send for RawBuf<T>
+    where T: Send {}
+
+unsafe impl<T> Sync for RawBuf<T>
+    where T: Sync {}
+
+impl<T> RawBuf<T> {
+    fn new() -> RawBuf<T> {
+        RawBuf {
+            ptr: NonNull::dangling(),
+            capacity: if mem::size_of::<T>() == 0 { usize::MAX } else { 0 },
+            _owns: PhantomData
+        }
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    // Moves the buffer to an allocation with room for capacity values. The
+    // values in the first min(old, new) slots are kept.
+    fn resize(&mut self, capacity: usize) {
+        if mem::size_of::<T>() == 0 || capacity == self.capacity {
+            return;
+        }
+
+        if capacity == 0 {
+            // SAFETY: the buffer was allocated with this layout, since its
+            // capacity isn't 0.
+            unsafe { alloc::dealloc(self.ptr() as *mut u8, self.layout()) };
+            self.ptr = NonNull::dangling();
+        }
+        else {
+            let layout = Layout::array::<T>(capacity).expect("Capacity overflow.");
+            let ptr = if self.capacity == 0 {
+                // SAFETY: the layout has a non-zero size, since T isn't
+                // zero-sized and capacity isn't 0.
+                unsafe { alloc::alloc(layout) }
+            }
+            else {
+                // SAFETY: the buffer was allocated with self.layout(), and
+                // Layout::array() checked that the new size fits in an isize.
+                unsafe { alloc::realloc(self.ptr() as *mut u8, self.layout(), layout.size()) }
+            };
+            self.ptr = match NonNull::new(ptr as *mut T) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout)
+            };
+        }
+        self.capacity = capacity;
+    }
+
+    // The layout of the current allocation.
+    fn layout(&self) -> Layout {
+        // This layout was checked when the buffer was allocated.
+        Layout::array::<T>(self.capacity).unwrap()
     }
 }
 
+impl<T> Drop for RawBuf<T> {
+    fn drop(&mut self) {
+        self.resize(0);
+    }
+}
 
+/// A growable array. When it fills up, its capacity is multiplied by a
+/// growth factor (2 by default), so pushing takes amortized O(1) time. When
+/// removing leaves it mostly empty, its capacity shrinks again.
+/// It dereferences to a slice, which provides iteration, slicing, sorting
+/// and so on.
 pub struct DynamicArray<T> {
-    size: usize,
-    items: Box<[T]>
+    buf: RawBuf<T>,
+    len: usize,
+    growth_factor: f64
 }
 
 impl<T> DynamicArray<T> {
+    /// Returns a new empty array. It doesn't allocate until something is
+    /// pushed onto it.
     pub fn new() -> DynamicArray<T> {
+        DynamicArray::with_growth_factor(2.0)
+    }
+
+    /// Returns a new empty array with room for capacity items.
+    pub fn with_capacity(capacity: usize) -> DynamicArray<T> {
+        let mut array = DynamicArray::new();
+        array.buf.resize(capacity);
+        array
+    }
+
+    /// Returns a new empty array whose capacity is multiplied by
+    /// growth_factor whenever it fills up. A smaller factor wastes less
+    /// memory but copies the items more often.
+    pub fn with_growth_factor(growth_factor: f64) -> DynamicArray<T> {
+        if !growth_factor.is_finite() || growth_factor <= 1.0 {
+            panic!("A dynamic array's growth factor must be a finite number greater than 1.");
+        }
         DynamicArray {
-            size: 1,
-            items: Box::new([T; 1])
+            buf: RawBuf::new(),
+            len: 0,
+            growth_factor
+        }
+    }
+
+    /// The number of items in the array.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the array is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of items the array can hold before it has to grow.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity
+    }
+
+    /// Adds an item to the end of the array.
+    pub fn push(&mut self, item: T) {
+        self.grow_if_full();
+        // SAFETY: there is room for at least one more item.
+        unsafe { ptr::write(self.buf.ptr().add(self.len), item) };
+        self.len += 1;
+    }
+
+    /// Removes the last item of the array and returns it. Returns None if
+    /// the array is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        // SAFETY: the slot at len held an item, which now belongs to nobody
+        // but the caller.
+        let item = unsafe { ptr::read(self.buf.ptr().add(self.len)) };
+        self.shrink_if_sparse();
+        Some(item)
+    }
+
+    /// Inserts an item at index, moving the items after it along by one.
+    /// Panics if index is greater than the length of the array.
+    pub fn insert(&mut self, index: usize, item: T) {
+        if index > self.len {
+            panic!("Cannot insert past the end of a dynamic array.");
+        }
+
+        self.grow_if_full();
+        // SAFETY: there is room for one more item, so the items from index
+        // on can move along by one, and the gap is then filled.
+        unsafe {
+            let slot = self.buf.ptr().add(index);
+            ptr::copy(slot, slot.add(1), self.len - index);
+            ptr::write(slot, item);
+        }
+        self.len += 1;
+    }
+
+    /// Removes the item at index and returns it, moving the items after it
+    /// back by one. Panics if there is no item at index.
+    pub fn remove(&mut self, index: usize) -> T {
+        self.check_index(index);
+
+        // SAFETY: index holds an item, which is moved out before the items
+        // after it are moved over its slot.
+        let item = unsafe {
+            let slot = self.buf.ptr().add(index);
+            let item = ptr::read(slot);
+            ptr::copy(slot.add(1), slot, self.len - index - 1);
+            item
+        };
+        self.len -= 1;
+        self.shrink_if_sparse();
+        item
+    }
+
+    // Multiplies the capacity by the growth factor if the array is full.
+    fn grow_if_full(&mut self) {
+        if self.len < self.buf.capacity {
+            return;
+        }
+        if mem::size_of::<T>() == 0 {
+            panic!("A dynamic array cannot hold more than usize::MAX items.");
+        }
+
+        let grown = (self.buf.capacity as f64 * self.growth_factor).ceil() as usize;
+        self.buf.resize(grown.max(self.len + 1));
+    }
+
+    // Divides the capacity by the growth factor if the array has become less
+    // than 1 / growth_factor² full. Waiting that long means pushing and
+    // popping around the boundary doesn't resize the array every time.
+    fn shrink_if_sparse(&mut self) {
+        if mem::size_of::<T>() == 0 {
+            return;
+        }
+
+        let capacity = self.buf.capacity as f64;
+        if (self.len as f64) < capacity / (self.growth_factor * self.growth_factor) {
+            let shrunk = (capacity / self.growth_factor) as usize;
+            self.buf.resize(shrunk.max(self.len));
+        }
+    }
+
+    // Panics if there is no item at index.
+    fn check_index(&self, index: usize) {
+        if index >= self.buf.capacity {
+            panic!("Attempted to access a dynamic array index beyond its capacity.");
+        }
+        else if index >= self.len {
+            panic!("Attempted to access unitialized dynamic array index.");
+        }
+    }
+}
+
+impl<T> Drop for DynamicArray<T> {
+    fn drop(&mut self) {
+        // SAFETY: the first len slots hold items. The buffer frees itself
+        // afterwards.
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.buf.ptr(), self.len)) };
+    }
+}
+
+impl<T> Deref for DynamicArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first len slots hold items, and the pointer is aligned
+        // and non-null even when nothing is allocated.
+        unsafe { slice::from_raw_parts(self.buf.ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for DynamicArray<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: see deref().
+        unsafe { slice::from_raw_parts_mut(self.buf.ptr(), self.len) }
+    }
+}
+
+// Indexing with a single position goes through check_index() to tell apart
+// the two ways it can fail.
+impl<T> Index<usize> for DynamicArray<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.check_index(index);
+        &self.deref()[index]
+    }
+}
+
+impl<T> IndexMut<usize> for DynamicArray<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.check_index(index);
+        &mut self.deref_mut()[index]
+    }
+}
+
+// A Deref type with its own Index<usize> impl doesn't get the slice's range
+// indexing, so pass ranges on to the slice here.
+macro_rules! impl_range_index {
+    ($($range:ty),*) => {
+        $(
+            impl<T> Index<$range> for DynamicArray<T> {
+                type Output = [T];
+
+                fn index(&self, range: $range) -> &[T] {
+                    &self.deref()[range]
+                }
+            }
+
+            impl<T> IndexMut<$range> for DynamicArray<T> {
+                fn index_mut(&mut self, range: $range) -> &mut [T] {
+                    &mut self.deref_mut()[range]
+                }
+            }
+        )*
+    };
+}
+
+impl_range_index!(Range<usize>, RangeFrom<usize>, RangeFull, RangeInclusive<usize>, RangeTo<usize>,
+    RangeToInclusive<usize>);
+
+impl<T> Default for DynamicArray<T> {
+    fn default() -> DynamicArray<T> {
+        DynamicArray::new()
+    }
+}
+
+impl<T> fmt::Debug for DynamicArray<T>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Extend<T> for DynamicArray<T> {
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        for item in iter {
+            self.push(item);
         }
     }
+}
 
-    pub fn append(item: T) {
+impl<T> FromIterator<T> for DynamicArray<T> {
+    fn from_iter<I>(iter: I) -> DynamicArray<T>
+        where I: IntoIterator<Item = T>
+    {
+        let mut array = DynamicArray::new();
+        array.extend(iter);
+        array
     }
 }
-*/
 
 #[cfg(test)]
 mod linked_list_tests {
@@ -47,201 +439,1484 @@ mod linked_list_tests {
 
     #[test]
     fn insertion_test() {
-        let mut l = LinkedList::<i32>::new();
-        l.insert(10);
-        assert_eq!(l.next().expect("Iterator should return the value in the list."),
-            10);
+        let mut l = LinkedList::new();
+        l.push_front(10);
+        assert_eq!(l.iter().next(), Some(&10));
+        assert_eq!(l.len(), 1);
+        assert_eq!(l.pop_front(), Some(10));
+        assert_eq!(l.pop_front(), None);
+        assert!(l.is_empty());
     }
 
     // The linked list should use an iterator to transparently return data.
     // The calling code will not know about Nodes.
     #[test]
     fn iteration_test() {
-        let mut l = LinkedList::<i32>::new();
+        let mut l = LinkedList::new();
 
         for i in 0..10 {
-            l.insert(i);
+            l.push_front(i);
         }
 
-        assert_eq!((&mut l).count(), 10);
+        // Iterators are independent of each other, so nested loops see the
+        // whole list every time.
+        let mut pairs = 0;
+        for _ in l.iter() {
+            for _ in l.iter() {
+                pairs += 1;
+            }
+        }
+        assert_eq!(pairs, 100);
 
-        l.reset();
+        for data in l.iter_mut() {
+            *data *= 2;
+        }
 
         // Insertion into the list occurs at the head, so the values inserted
         // above will be returned in reverse order.
         let mut i = 9;
         for data in l {
-            println!("Next list item: {}; Expecting: {}", data, i);
-            assert_eq!(data, i);
+            assert_eq!(data, i * 2);
             i -= 1;
         }
     }
+
+    #[test]
+    fn owned_items() {
+        let mut l: LinkedList<String> = LinkedList::new();
+        for word in ["spork", "fork", "spoon", "fork"] {
+            l.push_front(String::from(word));
+        }
+        assert!(l.contains(&String::from("spoon")));
+        assert!(!l.contains(&String::from("knife")));
+
+        // Only the first match is removed.
+        assert_eq!(l.remove_first(|word| word.starts_with('f')).as_deref(), Some("fork"));
+        assert_eq!(l.remove_first(|word| word.is_empty()), None);
+        assert_eq!(l.len(), 3);
+
+        l.reverse();
+        let words: Vec<&str> = l.iter().map(|word| word.as_str()).collect();
+        assert_eq!(words, vec!["spork", "fork", "spoon"]);
+    }
+
+    // Dropping a long list shouldn't overflow the stack.
+    #[test]
+    fn drop_long_list() {
+        let mut l = LinkedList::new();
+        for i in 0..1_000_000 {
+            l.push_front(i);
+        }
+        drop(l);
+    }
 }
 
-/// A linked list. Hides the low level details from the user.
-/// Only holds types that implement the Copy trait.
-// To make this easier, for now, the listed list will only support types that
-// implement Copy.
-// TODO: Learn how to properly handle linked data structures before attempting
-// graphs or trees*. This implementation is crap.
-// TODO: Implement a function to remove items from the list.
-// * A note on graphs and trees: It may be reasonable to make a purely Vec<T>
-// based adjacency list, eliminating the need for pointers.
-pub struct LinkedList<T>
-    where T: Copy
-{
-    // The head of the linked list.
-    head: Option<Rc<Node<T>>>,
-    // The current node of the linked list. Used by the iterator.
-    current: Option<Weak<Node<T>>>
+/// A singly linked list that owns its items. Items are added and removed at
+/// the front in O(1) time.
+pub struct LinkedList<T> {
+    head: Option<Box<Node<T>>>,
+    len: usize
 }
 
-struct Node<T>
-    where T: Copy
-{
+struct Node<T> {
     data: T,
-    next: Option<Rc<Node<T>>>
+    next: Option<Box<Node<T>>>
 }
 
-impl<T> Iterator for LinkedList<T>
-    where T: Copy
-{
-    type Item = T;
+impl<T> LinkedList<T> {
+    /// Returns a new empty list.
+    pub fn new() -> LinkedList<T> {
+        LinkedList {
+            head: None,
+            len: 0
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// The number of items in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        // If head is None, the list is empty. Return None.
-        if self.head.is_none() {
-            None
+    /// Returns true if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// The item at the front of the list.
+    pub fn front(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.data)
+    }
+
+    /// A mutable reference to the item at the front of the list.
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.data)
+    }
+
+    /// Inserts a new item at the front of the list.
+    pub fn push_front(&mut self, data: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(Node { data, next }));
+        self.len += 1;
+    }
+
+    /// Removes the item at the front of the list and returns it. Returns None
+    /// if the list is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.head.take()?;
+        self.head = node.next;
+        self.len -= 1;
+        Some(node.data)
+    }
+
+    /// Removes the first item for which pred returns true and returns it.
+    /// Returns None if there is no such item.
+    pub fn remove_first<P>(&mut self, mut pred: P) -> Option<T>
+        where P: FnMut(&T) -> bool
+    {
+        // Walk the links until one points at a matching node, then unhook
+        // that node.
+        let mut link = &mut self.head;
+        while link.as_ref().is_some_and(|node| !pred(&node.data)) {
+            // The loop condition has just found a node.
+            link = &mut link.as_mut().unwrap().next;
         }
-        // If head is Some but current is none, set current to head and return
-        // the data in the current (and head) node.
-        else if self.current.is_none() {
-            self.current = Some(Rc::downgrade(&(self.head.as_ref().unwrap())));
-            Some((*self.current.as_ref().unwrap().upgrade().unwrap()).data)
+
+        let node = link.take()?;
+        *link = node.next;
+        self.len -= 1;
+        Some(node.data)
+    }
+
+    /// Reverses the order of the items in place.
+    pub fn reverse(&mut self) {
+        let mut reversed = None;
+        let mut rest = self.head.take();
+        while let Some(mut node) = rest {
+            rest = node.next.take();
+            node.next = reversed;
+            reversed = Some(node);
         }
-        // If current is not none but its weak reference is, something went wrong.
-        else if self.current.as_ref().unwrap().upgrade().is_none() {
-            panic!("The weak reference in a LinkedList's current node is invalid.\
-                   This is probably a bug in LinkedList<T>'s implementation.");
+        self.head = reversed;
+    }
+
+    /// Returns true if the list holds an item equal to item.
+    pub fn contains(&self, item: &T) -> bool
+        where T: PartialEq
+    {
+        self.iter().any(|data| data == item)
+    }
+
+    /// Get an iterator over the items, from front to back.
+    pub fn iter(&self) -> LinkedListIter<'_, T> {
+        LinkedListIter {
+            next: self.head.as_deref(),
+            len: self.len
         }
-        // We know current is not none if we reach this, so we can use unwrap().
-        // If current.next is none (i.e. we've reached the end of the list), return None.
-        else if self.current.as_ref().unwrap().upgrade().unwrap().next.is_none() {
-            None
+    }
+
+    /// Get an iterator over mutable references to the items, from front to
+    /// back.
+    pub fn iter_mut(&mut self) -> LinkedListIterMut<'_, T> {
+        LinkedListIterMut {
+            next: self.head.as_deref_mut(),
+            len: self.len
         }
-        // Else, advance to the next node and return self.current.data.
-        else {
-            // We know there's something to unwrap here because the branches
-            // above this one have ensured it.
-            self.current =
-                Some(
-                    Rc::downgrade(
-                        &((*(self.current.as_ref().unwrap().upgrade().unwrap())).next.as_ref().unwrap())
-                    )
-                );
-            // We know there's something to unwrap() because we just put it there.
-            Some((*self.current.as_ref().unwrap().upgrade().unwrap()).data)
+    }
+}
+
+// The default drop would recurse once per node, which can overflow the stack
+// for a long list.
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        let mut rest = self.head.take();
+        while let Some(mut node) = rest {
+            rest = node.next.take();
         }
     }
 }
 
-impl<T> LinkedList<T>
-    where T: Copy
+impl<T> Default for LinkedList<T> {
+    fn default() -> LinkedList<T> {
+        LinkedList::new()
+    }
+}
+
+impl<T> fmt::Debug for LinkedList<T>
+    where T: fmt::Debug
 {
-    /// Returns a new empty list.
-    pub fn new() -> LinkedList<T> {
-        LinkedList::<T> {
-            head: None,
-            current: None
-        }
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the items of a [`LinkedList`], from front to back.
+/// Created by LinkedList::iter().
+pub struct LinkedListIter<'a, T> {
+    next: Option<&'a Node<T>>,
+    len: usize
+}
+
+impl<'a, T> Iterator for LinkedListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        self.len -= 1;
+        Some(&node.data)
     }
 
-    /// Resets the list's internal pointer.
-    /// Allows the list to be iterated over from the start.
-    pub fn reset(&mut self) {
-        self.current = None
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
+}
 
-    /// Inserts a new item at the head of the list.
-    pub fn insert(&mut self, data: T) {
-        let mut n: Node<T> = Node {
-            data,
-            next: None
-        };
+/// Iterator over mutable references to the items of a [`LinkedList`], from
+/// front to back. Created by LinkedList::iter_mut().
+pub struct LinkedListIterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+    len: usize
+}
 
-        match &self.head {
-            Some(h) => {
-                // Put a reference to the head node in n.next.
-                n.next = Some(Rc::clone(&h));
-                // Make n the head node.
-                self.head = Some(Rc::new(n));
-            },
-            None => { 
-                self.head = Some(Rc::new(n));
-            }
-        }
+impl<'a, T> Iterator for LinkedListIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        let node = self.next.take()?;
+        self.next = node.next.as_deref_mut();
+        self.len -= 1;
+        Some(&mut node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+/// Iterator that takes the items out of a [`LinkedList`], from front to
+/// back. Created by LinkedList::into_iter().
+pub struct LinkedListIntoIter<T> {
+    list: LinkedList<T>
+}
+
+impl<T> Iterator for LinkedListIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = LinkedListIntoIter<T>;
+
+    fn into_iter(self) -> LinkedListIntoIter<T> {
+        LinkedListIntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = LinkedListIter<'a, T>;
+
+    fn into_iter(self) -> LinkedListIter<'a, T> {
+        self.iter()
     }
 }
 
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = LinkedListIterMut<'a, T>;
+
+    fn into_iter(self) -> LinkedListIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
 
 #[cfg(test)]
-mod vec_stack_tests {
+mod persistent_list_tests {
     use super::*;
 
-    // Push some data to the stack. Pop it all off the stack. Verify that all
-    // of the data is returned in the correct order.
+    fn contents<T>(list: &PersistentList<T>) -> Vec<T>
+        where T: Clone
+    {
+        list.iter().cloned().collect()
+    }
+
     #[test]
-    fn push_and_pop() {
-        let mut stack = VecStack::new();
+    fn old_versions_stay_valid() {
+        let empty = PersistentList::new();
+        let one = empty.cons(1);
+        let two = one.cons(2);
+        let other_two = one.cons(20);
 
-        // Fill the stack.
-        for i in 0..10 {
-            stack.push(i);
-        }
+        assert!(empty.is_empty());
+        assert_eq!(contents(&one), vec![1]);
+        assert_eq!(contents(&two), vec![2, 1]);
+        assert_eq!(contents(&other_two), vec![20, 1]);
+        assert_eq!(two.head(), Some(&2));
+        assert_eq!(two.len(), 2);
 
-        // Empty the stack and make assertions.
-        for i in (0..10).rev() {
-            assert_eq!(i, stack.pop());
-        }
+        // Both versions share the node holding 1.
+        let (tail, other_tail) = (two.tail().unwrap(), other_two.tail().unwrap());
+        assert!(std::ptr::eq(tail.head().unwrap(), other_tail.head().unwrap()));
+        assert_eq!(tail, one);
+        assert!(empty.tail().is_none());
     }
 
-    // Pop from an empty stack. This is an error on the part of the caller and
-    // should result in a panic.
     #[test]
-    #[should_panic(expected = "Cannot pop from an empty stack.")]
-    fn pop_from_empty() {
-        let mut stack:VecStack<i32> = VecStack::new();
-        stack.pop();
-    }
-}
+    fn append() {
+        let front: PersistentList<i32> = [1, 2].into_iter().collect();
+        let back: PersistentList<i32> = [3, 4, 5].into_iter().collect();
+        let joined = front.append(&back);
+        assert_eq!(contents(&joined), vec![1, 2, 3, 4, 5]);
+        assert_eq!(joined.len(), 5);
 
-/// A vector-based stack implementation.
-/// Data stored on the stack is owned by the stack. Ownership of data returned
-/// by pop() will be given to the caller of pop().
-// Implementing a queue this way probably wouldn't work but this should be easy.
-// Considering that vec<T> implements its own push and pop() this is trivial...
-pub struct VecStack<T> {
-    // The vector that stores our data.
-    vector: Vec<T>,
-}
+        // The back list is shared rather than copied.
+        let shared = joined.tail().unwrap().tail().unwrap();
+        assert!(std::ptr::eq(shared.head().unwrap(), back.head().unwrap()));
+        assert_eq!(contents(&front), vec![1, 2]);
+    }
 
-impl<T> VecStack<T> {
-    /// Returns a new empty stack.
-    pub fn new() -> VecStack<T> {
-        VecStack {
-            vector: Vec::new()
+    // Use the list as an undo history.
+    #[test]
+    fn undo_history() {
+        let mut history = PersistentList::new();
+        let mut saved = Vec::new();
+        for edit in ["type a", "type b", "delete", "type c"] {
+            history = history.cons(String::from(edit));
+            saved.push(history.clone());
         }
+
+        // Undo twice, then make a different edit.
+        let undone = history.tail().unwrap().tail().unwrap();
+        let redone = undone.cons(String::from("type d"));
+        assert_eq!(redone.head().map(|s| s.as_str()), Some("type d"));
+        assert_eq!(redone.len(), 3);
+        drop(history);
+
+        assert_eq!(saved[3].len(), 4);
+        assert_eq!(saved[3].head().map(|s| s.as_str()), Some("type c"));
+        assert_eq!(saved[1], undone);
     }
 
-    /// Puts an item on the top of the stack.
-    pub fn push(&mut self, item: T) {
-        self.vector.push(item);
+    // Dropping long lists shouldn't overflow the stack, whether or not part
+    // of them is shared.
+    #[test]
+    fn drop_long_list() {
+        let shared: PersistentList<usize> = (0..1_000_000).collect();
+        let longer = (0..1_000_000).fold(shared.clone(), |list, i| list.cons(i));
+        drop(longer);
+        assert_eq!(shared.len(), 1_000_000);
+        drop(shared);
     }
+}
 
-    /// Removes and returns the item on the top of the stack.
-    // TODO: This should return an Option<T>, there should be a way to
-    // get the number of items on the stack, or both.
+/// An immutable singly linked list. cons(), tail() and append() return new
+/// lists that share nodes with the old ones instead of changing them, so
+/// every old version of a list stays valid. This makes it useful for undo
+/// histories and backtracking. Cloning a list takes O(1) time.
+pub struct PersistentList<T> {
+    head: Option<Rc<PersistentNode<T>>>,
+    len: usize
+}
+
+struct PersistentNode<T> {
+    data: T,
+    next: Option<Rc<PersistentNode<T>>>
+}
+
+impl<T> PersistentList<T> {
+    /// Returns a new empty list.
+    pub fn new() -> PersistentList<T> {
+        PersistentList {
+            head: None,
+            len: 0
+        }
+    }
+
+    /// The number of items in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// The first item of the list.
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.data)
+    }
+
+    /// Returns a new list made of item followed by this list, in O(1) time.
+    pub fn cons(&self, item: T) -> PersistentList<T> {
+        PersistentList {
+            head: Some(Rc::new(PersistentNode { data: item, next: self.head.clone() })),
+            len: self.len + 1
+        }
+    }
+
+    /// Returns the list without its first item, in O(1) time. Returns None
+    /// if the list is empty.
+    pub fn tail(&self) -> Option<PersistentList<T>> {
+        self.head.as_ref().map(|node| PersistentList {
+            head: node.next.clone(),
+            len: self.len - 1
+        })
+    }
+
+    /// Returns a new list made of the items of this list followed by other.
+    /// other is shared, while this list's items are copied, so this takes
+    /// O(n) time for n items in this list.
+    pub fn append(&self, other: &PersistentList<T>) -> PersistentList<T>
+        where T: Clone
+    {
+        let items: Vec<&T> = self.iter().collect();
+        items.into_iter().rev().fold(other.clone(), |list, item| list.cons(item.clone()))
+    }
+
+    /// Get an iterator over the items, from first to last.
+    pub fn iter(&self) -> PersistentListIter<'_, T> {
+        PersistentListIter {
+            next: self.head.as_deref(),
+            len: self.len
+        }
+    }
+}
+
+// Only the list handle is copied; the nodes are shared.
+impl<T> Clone for PersistentList<T> {
+    fn clone(&self) -> PersistentList<T> {
+        PersistentList {
+            head: self.head.clone(),
+            len: self.len
+        }
+    }
+}
+
+// The default drop would recurse once per node, which can overflow the stack
+// for a long list. Nodes are freed until one is reached that another list
+// still shares.
+impl<T> Drop for PersistentList<T> {
+    fn drop(&mut self) {
+        let mut rest = self.head.take();
+        while let Some(node) = rest {
+            rest = match Rc::try_unwrap(node) {
+                Ok(mut node) => node.next.take(),
+                Err(_) => None
+            };
+        }
+    }
+}
+
+impl<T> Default for PersistentList<T> {
+    fn default() -> PersistentList<T> {
+        PersistentList::new()
+    }
+}
+
+impl<T> fmt::Debug for PersistentList<T>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> PartialEq for PersistentList<T>
+    where T: PartialEq
+{
+    fn eq(&self, other: &PersistentList<T>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+/// Builds a list holding the items in the order the iterator gives them.
+impl<T> FromIterator<T> for PersistentList<T> {
+    fn from_iter<I>(iter: I) -> PersistentList<T>
+        where I: IntoIterator<Item = T>
+    {
+        let items: Vec<T> = iter.into_iter().collect();
+        items.into_iter().rev().fold(PersistentList::new(), |list, item| list.cons(item))
+    }
+}
+
+/// Iterator over the items of a [`PersistentList`], from first to last.
+/// Created by PersistentList::iter().
+pub struct PersistentListIter<'a, T> {
+    next: Option<&'a PersistentNode<T>>,
+    len: usize
+}
+
+impl<'a, T> Iterator for PersistentListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        self.len -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PersistentList<T> {
+    type Item = &'a T;
+    type IntoIter = PersistentListIter<'a, T>;
+
+    fn into_iter(self) -> PersistentListIter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod doubly_linked_list_tests {
+    use super::*;
+
+    // Collect the list's contents, front to back.
+    fn contents<T: Clone>(list: &DoublyLinkedList<T>, nodes: &ListArena<T>) -> Vec<T> {
+        list.iter(nodes).cloned().collect()
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        assert!(list.pop_front(&mut nodes).is_none());
+        assert!(list.pop_back(&mut nodes).is_none());
+
+        // Use a type that isn't Copy.
+        for i in 0..3 {
+            list.push_back(&mut nodes, i.to_string());
+            list.push_front(&mut nodes, (-i).to_string());
+        }
+        assert_eq!(list.len(&nodes), 6);
+        assert_eq!(contents(&list, &nodes), vec!["-2", "-1", "0", "0", "1", "2"]);
+        assert_eq!(list.front(&nodes).unwrap(), "-2");
+        assert_eq!(list.back(&nodes).unwrap(), "2");
+
+        assert_eq!(list.pop_front(&mut nodes).unwrap(), "-2");
+        assert_eq!(list.pop_back(&mut nodes).unwrap(), "2");
+        list.back_mut(&mut nodes).unwrap().push('!');
+        assert_eq!(contents(&list, &nodes), vec!["-1", "0", "0", "1!"]);
+
+        while list.pop_back(&mut nodes).is_some() {}
+        assert!(list.is_empty());
+        assert!(list.front(&nodes).is_none());
+        list.push_front(&mut nodes, String::from("again"));
+        assert_eq!(contents(&list, &nodes), vec!["again"]);
+    }
+
+    #[test]
+    fn remove_and_insert_by_key() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        let keys: Vec<ListKey<i32>> = (0..5).map(|i| list.push_back(&mut nodes, i)).collect();
+
+        assert_eq!(list.remove(&mut nodes, keys[2]), Some(2));
+        assert_eq!(list.remove(&mut nodes, keys[0]), Some(0));
+        assert_eq!(list.remove(&mut nodes, keys[4]), Some(4));
+        // A removed element's key no longer reaches anything.
+        assert_eq!(list.remove(&mut nodes, keys[2]), None);
+        assert!(list.get(&nodes, keys[2]).is_none());
+        assert_eq!(contents(&list, &nodes), vec![1, 3]);
+
+        list.insert_after(&mut nodes, keys[1], 2).unwrap();
+        list.insert_before(&mut nodes, keys[1], 0).unwrap();
+        let four = list.insert_after(&mut nodes, keys[3], 4).unwrap();
+        assert!(list.insert_after(&mut nodes, keys[0], 100).is_none());
+        *list.get_mut(&mut nodes, four).unwrap() *= 10;
+        assert_eq!(contents(&list, &nodes), vec![0, 1, 2, 3, 40]);
+        assert_eq!(list.iter(&nodes).rev().copied().collect::<Vec<_>>(), vec![40, 3, 2, 1, 0]);
+    }
+
+    // Moving elements between lists that share an arena keeps their keys.
+    #[test]
+    fn keys_after_split_off_and_append() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        let keys: Vec<ListKey<i32>> = (0..4).map(|i| list.push_back(&mut nodes, i)).collect();
+        let mut tail = list.split_off(&mut nodes, keys[2]).unwrap();
+        assert_eq!(tail.remove(&mut nodes, keys[3]), Some(3));
+        *tail.get_mut(&mut nodes, keys[2]).unwrap() = 20;
+        assert_eq!(list.remove(&mut nodes, keys[1]), Some(1));
+
+        list.append(&mut nodes, &mut tail);
+        assert!(list.insert_after(&mut nodes, keys[2], 30).is_some());
+        assert_eq!(contents(&list, &nodes), vec![0, 20, 30]);
+        assert_eq!(list.remove(&mut nodes, keys[2]), Some(20));
+        assert_eq!(nodes.len(), 2);
+    }
+
+    // A key to the front or back of another list sharing the arena is
+    // turned away.
+    #[test]
+    fn remove_key_from_other_list() {
+        let mut nodes = ListArena::new();
+        let mut a = DoublyLinkedList::new();
+        let mut b = DoublyLinkedList::new();
+        a.push_back(&mut nodes, 0);
+        let front = b.push_back(&mut nodes, 1);
+        let back = b.push_back(&mut nodes, 2);
+        assert_eq!(a.remove(&mut nodes, front), None);
+        assert_eq!(a.remove(&mut nodes, back), None);
+        assert_eq!(contents(&a, &nodes), vec![0]);
+        assert_eq!(contents(&b, &nodes), vec![1, 2]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "The key was created by a different arena.")]
+    fn remove_key_from_other_arena() {
+        let mut small = ListArena::new();
+        let mut large = ListArena::new();
+        let mut a = DoublyLinkedList::new();
+        let mut b = DoublyLinkedList::new();
+        a.push_back(&mut small, 0);
+        let keys: Vec<ListKey<i32>> = (0..10).map(|i| b.push_back(&mut large, i)).collect();
+        a.remove(&mut small, keys[9]);
+    }
+
+    #[test]
+    fn append_and_split_off() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        let mut other = DoublyLinkedList::new();
+        for i in 0..3 {
+            list.push_back(&mut nodes, i);
+            other.push_back(&mut nodes, i + 3);
+        }
+        list.append(&mut nodes, &mut other);
+        assert!(other.is_empty());
+        assert_eq!(list.len(&nodes), 6);
+        assert_eq!(contents(&list, &nodes), vec![0, 1, 2, 3, 4, 5]);
+
+        let key = list.cursor_front(&nodes).nth_key(4).unwrap();
+        let tail = list.split_off(&mut nodes, key).unwrap();
+        assert_eq!(list.len(&nodes), 4);
+        assert_eq!(tail.len(&nodes), 2);
+        assert_eq!(contents(&list, &nodes), vec![0, 1, 2, 3]);
+        assert_eq!(contents(&tail, &nodes), vec![4, 5]);
+        assert_eq!(*list.back(&nodes).unwrap(), 3);
+
+        let front = list.cursor_front(&nodes).key().unwrap();
+        let mut all = list.split_off(&mut nodes, front).unwrap();
+        assert!(list.is_empty());
+        assert_eq!(list.len(&nodes), 0);
+        assert_eq!(contents(&all, &nodes), vec![0, 1, 2, 3]);
+
+        // Appending an empty list, or to one, keeps the lengths right.
+        all.append(&mut nodes, &mut list);
+        list.append(&mut nodes, &mut all);
+        assert_eq!(list.len(&nodes), 4);
+        list.clear(&mut nodes);
+        assert!(list.is_empty());
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn iter_size_hint() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        for i in 0..5 {
+            list.push_back(&mut nodes, i);
+        }
+        let mut iter = list.iter(&nodes);
+        assert_eq!(iter.size_hint(), (5, Some(5)));
+        iter.next();
+        iter.next_back();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.collect::<Vec<_>>(), vec![&1, &2, &3]);
+    }
+
+    #[test]
+    fn cursor() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        for i in 0..3 {
+            list.push_back(&mut nodes, i);
+        }
+
+        let mut cursor = list.cursor_front(&nodes);
+        assert_eq!(cursor.current(), Some(&0));
+        assert_eq!(cursor.peek_prev(), None);
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&2));
+        assert_eq!(cursor.peek_prev(), Some(&1));
+        // Moving past the back lands on the "ghost" position, which sits
+        // between the back and the front.
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&0));
+        assert_eq!(cursor.peek_prev(), Some(&2));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&0));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&2));
+
+        let empty: DoublyLinkedList<i32> = DoublyLinkedList::new();
+        let mut cursor = empty.cursor_back(&nodes);
+        cursor.move_prev();
+        assert!(cursor.current().is_none());
+    }
+
+    #[test]
+    fn cursor_mut() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        let one = list.push_back(&mut nodes, 1);
+        list.push_back(&mut nodes, 3);
+
+        let mut cursor = list.cursor_at_mut(&mut nodes, one).unwrap();
+        cursor.insert_after(2);
+        cursor.insert_before(0);
+        *cursor.current().unwrap() *= 10;
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        // Removing moves the cursor on to the next element.
+        assert_eq!(cursor.current(), Some(&mut 3));
+        cursor.move_next();
+        // At the ghost position, inserting after means at the front and
+        // inserting before means at the back.
+        cursor.insert_after(-1);
+        cursor.insert_before(4);
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(contents(&list, &nodes), vec![-1, 0, 10, 3, 4]);
+        assert_eq!(list.len(&nodes), 5);
+    }
+
+    #[test]
+    fn cursor_splice_and_split() {
+        let mut nodes = ListArena::new();
+        let mut list = DoublyLinkedList::new();
+        let mut middle = DoublyLinkedList::new();
+        for i in 0..2 {
+            list.push_back(&mut nodes, i.to_string());
+        }
+        list.push_back(&mut nodes, String::from("5"));
+        let keys: Vec<ListKey<String>> = (2..5).map(|i| middle.push_back(&mut nodes, i.to_string())).collect();
+
+        let mut cursor = list.cursor_front_mut(&mut nodes);
+        cursor.move_next();
+        cursor.splice_after(middle);
+        assert_eq!(cursor.current().unwrap(), "1");
+        let tail = cursor.split_after();
+        assert_eq!(contents(&list, &nodes), vec!["0", "1"]);
+        assert_eq!(contents(&tail, &nodes), vec!["2", "3", "4", "5"]);
+        assert_eq!(tail.get(&nodes, keys[1]).unwrap(), "3");
+
+        // Splicing at the ghost position puts the other list at the front.
+        let mut cursor = list.cursor_back_mut(&mut nodes);
+        cursor.move_next();
+        cursor.splice_after(tail);
+        assert_eq!(contents(&list, &nodes), vec!["2", "3", "4", "5", "0", "1"]);
+        assert_eq!(list.len(&nodes), 6);
+
+        // Splitting at the back leaves nothing to move.
+        let mut cursor = list.cursor_back_mut(&mut nodes);
+        assert!(cursor.split_after().is_empty());
+        assert_eq!(list.len(&nodes), 6);
+    }
+}
+
+/// An element of a [`DoublyLinkedList`] along with its links. Only reachable
+/// through the list.
+pub struct ListNode<T> {
+    data: T,
+    prev: Option<ListKey<T>>,
+    next: Option<ListKey<T>>
+}
+
+/// Refers to an element of a [`DoublyLinkedList`]. Stays valid until that
+/// element is removed, including while it moves between lists that share a
+/// [`ListArena`].
+pub type ListKey<T> = Key<ListNode<T>>;
+
+/// The arena that one or more [`DoublyLinkedList`]s keep their nodes in.
+pub type ListArena<T> = Arena<ListNode<T>>;
+
+/// A doubly linked list whose nodes live in a [`ListArena`], which is passed
+/// to each of its methods. Elements can be added and removed at either end in
+/// O(1), and the key returned when an element is added can be used to reach
+/// or remove it later, also in O(1).
+/// A [`Cursor`] or [`CursorMut`] can walk the list in both directions and edit
+/// it in place.
+/// Any number of lists can share one arena. Moving elements between them
+/// (append(), split_off() and the cursor's splice_after() and split_after())
+/// only relinks the ends, in O(1) time, and keys to the moved elements keep
+/// working. A key must only be used with the list that holds its element; see
+/// remove().
+/// Dropping a list leaves its nodes in the arena, so use clear() to free
+/// them while the arena is still in use.
+// Keeping the nodes in an arena gives each one a single owner, so the links
+// in both directions can be plain keys instead of Rc/Weak pointers.
+pub struct DoublyLinkedList<T> {
+    head: Option<ListKey<T>>,
+    tail: Option<ListKey<T>>,
+    // The number of elements, or None if the list was split since it was
+    // last counted. A split can't tell how many elements it moves without
+    // walking them.
+    len: Cell<Option<usize>>
+}
+
+impl<T> DoublyLinkedList<T> {
+    /// Returns a new empty list.
+    pub fn new() -> DoublyLinkedList<T> {
+        DoublyLinkedList {
+            head: None,
+            tail: None,
+            len: Cell::new(Some(0))
+        }
+    }
+
+    /// The number of elements in the list. Takes O(1) time, except for the
+    /// first call after the list was split, which counts the elements.
+    pub fn len(&self, nodes: &ListArena<T>) -> usize {
+        if let Some(len) = self.len.get() {
+            return len;
+        }
+        let mut len = 0;
+        let mut current = self.head;
+        while let Some(key) = current {
+            len += 1;
+            current = nodes[key].next;
+        }
+        self.len.set(Some(len));
+        len
+    }
+
+    /// Returns true if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns a reference to the element at the front of the list.
+    pub fn front<'a>(&self, nodes: &'a ListArena<T>) -> Option<&'a T> {
+        self.head.map(|key| &nodes[key].data)
+    }
+
+    /// Returns a mutable reference to the element at the front of the list.
+    pub fn front_mut<'a>(&self, nodes: &'a mut ListArena<T>) -> Option<&'a mut T> {
+        self.head.map(|key| &mut nodes[key].data)
+    }
+
+    /// Returns a reference to the element at the back of the list.
+    pub fn back<'a>(&self, nodes: &'a ListArena<T>) -> Option<&'a T> {
+        self.tail.map(|key| &nodes[key].data)
+    }
+
+    /// Returns a mutable reference to the element at the back of the list.
+    pub fn back_mut<'a>(&self, nodes: &'a mut ListArena<T>) -> Option<&'a mut T> {
+        self.tail.map(|key| &mut nodes[key].data)
+    }
+
+    /// Returns a reference to the element with the given key, or None if it
+    /// has been removed.
+    pub fn get<'a>(&self, nodes: &'a ListArena<T>, key: ListKey<T>) -> Option<&'a T> {
+        nodes.get(key).map(|node| &node.data)
+    }
+
+    /// Returns a mutable reference to the element with the given key, or None
+    /// if it has been removed.
+    pub fn get_mut<'a>(&self, nodes: &'a mut ListArena<T>, key: ListKey<T>) -> Option<&'a mut T> {
+        nodes.get_mut(key).map(|node| &mut node.data)
+    }
+
+    /// Adds an element to the front of the list and returns its key.
+    pub fn push_front(&mut self, nodes: &mut ListArena<T>, data: T) -> ListKey<T> {
+        self.link(nodes, data, None, self.head)
+    }
+
+    /// Adds an element to the back of the list and returns its key.
+    pub fn push_back(&mut self, nodes: &mut ListArena<T>, data: T) -> ListKey<T> {
+        self.link(nodes, data, self.tail, None)
+    }
+
+    /// Inserts an element right after the element with the given key and
+    /// returns the new element's key. Returns None (and drops the data) if
+    /// the given key's element has been removed.
+    pub fn insert_after(&mut self, nodes: &mut ListArena<T>, key: ListKey<T>, data: T)
+        -> Option<ListKey<T>>
+    {
+        let next = nodes.get(key)?.next;
+        Some(self.link(nodes, data, Some(key), next))
+    }
+
+    /// Inserts an element right before the element with the given key and
+    /// returns the new element's key. Returns None (and drops the data) if
+    /// the given key's element has been removed.
+    pub fn insert_before(&mut self, nodes: &mut ListArena<T>, key: ListKey<T>, data: T)
+        -> Option<ListKey<T>>
+    {
+        let prev = nodes.get(key)?.prev;
+        Some(self.link(nodes, data, prev, Some(key)))
+    }
+
+    /// Removes and returns the element at the front of the list.
+    pub fn pop_front(&mut self, nodes: &mut ListArena<T>) -> Option<T> {
+        self.head.and_then(|key| self.remove(nodes, key))
+    }
+
+    /// Removes and returns the element at the back of the list.
+    pub fn pop_back(&mut self, nodes: &mut ListArena<T>) -> Option<T> {
+        self.tail.and_then(|key| self.remove(nodes, key))
+    }
+
+    /// Removes and returns the element with the given key, or None if it has
+    /// already been removed.
+    /// The key has to belong to this list. A key to the front or back of
+    /// another list sharing the arena also returns None, but one to an
+    /// element in the middle of another list removes it from that list and
+    /// leaves both lists' lengths wrong.
+    /// # Panics
+    /// In debug builds, panics if the key was handed out by a different arena.
+    pub fn remove(&mut self, nodes: &mut ListArena<T>, key: ListKey<T>) -> Option<T> {
+        let node = nodes.get(key)?;
+        if self.is_empty() ||
+            (node.prev.is_none() && self.head != Some(key)) ||
+            (node.next.is_none() && self.tail != Some(key))
+        {
+            return None;
+        }
+
+        // get() has made sure the node exists.
+        let node = nodes.remove(key).unwrap();
+        match node.prev {
+            Some(prev) => nodes[prev].next = node.next,
+            None => self.head = node.next
+        }
+        match node.next {
+            Some(next) => nodes[next].prev = node.prev,
+            None => self.tail = node.prev
+        }
+        self.change_len(|len| len - 1);
+        Some(node.data)
+    }
+
+    /// Removes every element of the list from the arena.
+    pub fn clear(&mut self, nodes: &mut ListArena<T>) {
+        while self.pop_front(nodes).is_some() {}
+    }
+
+    /// Moves every element of other to the back of this list in O(1) time,
+    /// leaving other empty. Both lists have to share the arena.
+    pub fn append(&mut self, nodes: &mut ListArena<T>, other: &mut DoublyLinkedList<T>) {
+        let other = mem::take(other);
+        self.splice_after_node(nodes, self.tail, other);
+    }
+
+    /// Splits the list in two at the element with the given key, in O(1)
+    /// time. That element and everything after it are moved into the returned
+    /// list, which shares the arena. Returns None if the key's element has
+    /// been removed.
+    pub fn split_off(&mut self, nodes: &mut ListArena<T>, key: ListKey<T>)
+        -> Option<DoublyLinkedList<T>>
+    {
+        let prev = nodes.get(key)?.prev;
+        Some(self.split_after_node(nodes, prev))
+    }
+
+    /// Get an iterator over the elements of the list, from front to back.
+    pub fn iter<'a>(&self, nodes: &'a ListArena<T>) -> DoublyLinkedListIter<'a, T> {
+        DoublyLinkedListIter {
+            nodes,
+            front: self.head,
+            back: self.tail,
+            remaining: self.len(nodes)
+        }
+    }
+
+    /// Get a cursor pointing at the front of the list (or at the "ghost"
+    /// position if the list is empty).
+    pub fn cursor_front<'a>(&'a self, nodes: &'a ListArena<T>) -> Cursor<'a, T> {
+        Cursor { list: self, nodes, current: self.head }
+    }
+
+    /// Get a cursor pointing at the back of the list (or at the "ghost"
+    /// position if the list is empty).
+    pub fn cursor_back<'a>(&'a self, nodes: &'a ListArena<T>) -> Cursor<'a, T> {
+        Cursor { list: self, nodes, current: self.tail }
+    }
+
+    /// Get a cursor pointing at the element with the given key, or None if it
+    /// has been removed.
+    pub fn cursor_at<'a>(&'a self, nodes: &'a ListArena<T>, key: ListKey<T>) -> Option<Cursor<'a, T>> {
+        nodes.get(key)?;
+        Some(Cursor { list: self, nodes, current: Some(key) })
+    }
+
+    /// Get a mutable cursor pointing at the front of the list (or at the
+    /// "ghost" position if the list is empty).
+    pub fn cursor_front_mut<'a>(&'a mut self, nodes: &'a mut ListArena<T>) -> CursorMut<'a, T> {
+        let current = self.head;
+        CursorMut { list: self, nodes, current }
+    }
+
+    /// Get a mutable cursor pointing at the back of the list (or at the
+    /// "ghost" position if the list is empty).
+    pub fn cursor_back_mut<'a>(&'a mut self, nodes: &'a mut ListArena<T>) -> CursorMut<'a, T> {
+        let current = self.tail;
+        CursorMut { list: self, nodes, current }
+    }
+
+    /// Get a mutable cursor pointing at the element with the given key, or
+    /// None if it has been removed.
+    pub fn cursor_at_mut<'a>(&'a mut self, nodes: &'a mut ListArena<T>, key: ListKey<T>)
+        -> Option<CursorMut<'a, T>>
+    {
+        nodes.get(key)?;
+        Some(CursorMut { list: self, nodes, current: Some(key) })
+    }
+
+    // Store a new node between prev and next (either of which may be None for
+    // the ends of the list) and point its neighbors at it.
+    fn link(&mut self, nodes: &mut ListArena<T>, data: T, prev: Option<ListKey<T>>,
+        next: Option<ListKey<T>>) -> ListKey<T>
+    {
+        let key = nodes.insert(ListNode { data, prev, next });
+        match prev {
+            Some(prev) => nodes[prev].next = Some(key),
+            None => self.head = Some(key)
+        }
+        match next {
+            Some(next) => nodes[next].prev = Some(key),
+            None => self.tail = Some(key)
+        }
+        self.change_len(|len| len + 1);
+        key
+    }
+
+    // Apply a change to the length, if it is known.
+    fn change_len<F>(&self, change: F)
+        where F: FnOnce(usize) -> usize
+    {
+        self.len.set(self.len.get().map(change));
+    }
+
+    // Returns the key of the node after the given position, where None is the
+    // "ghost" position before the front.
+    fn next_of(&self, nodes: &ListArena<T>, position: Option<ListKey<T>>) -> Option<ListKey<T>> {
+        match position {
+            Some(key) => nodes[key].next,
+            None => self.head
+        }
+    }
+
+    // Returns the key of the node before the given position, where None is
+    // the "ghost" position after the back.
+    fn prev_of(&self, nodes: &ListArena<T>, position: Option<ListKey<T>>) -> Option<ListKey<T>> {
+        match position {
+            Some(key) => nodes[key].prev,
+            None => self.tail
+        }
+    }
+
+    // Link every element of other in after the given position (None means
+    // at the front).
+    fn splice_after_node(&mut self, nodes: &mut ListArena<T>, after: Option<ListKey<T>>,
+        other: DoublyLinkedList<T>)
+    {
+        let (first, last) = match (other.head, other.tail) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return
+        };
+        let next = self.next_of(nodes, after);
+        nodes[first].prev = after;
+        nodes[last].next = next;
+        match after {
+            Some(after) => nodes[after].next = Some(first),
+            None => self.head = Some(first)
+        }
+        match next {
+            Some(next) => nodes[next].prev = Some(last),
+            None => self.tail = Some(last)
+        }
+        let len = self.len.get().zip(other.len.get()).map(|(a, b)| a + b);
+        self.len.set(len);
+    }
+
+    // Unlink every element after the given position (None means the whole
+    // list) into a new list.
+    fn split_after_node(&mut self, nodes: &mut ListArena<T>, after: Option<ListKey<T>>)
+        -> DoublyLinkedList<T>
+    {
+        let first = match self.next_of(nodes, after) {
+            Some(first) => first,
+            None => return DoublyLinkedList::new()
+        };
+        let split = DoublyLinkedList {
+            head: Some(first),
+            tail: self.tail,
+            len: Cell::new(None)
+        };
+        nodes[first].prev = None;
+        match after {
+            Some(after) => nodes[after].next = None,
+            None => self.head = None
+        }
+        self.tail = after;
+
+        // Both lengths are only known without counting when the whole list
+        // moves.
+        if after.is_none() {
+            split.len.set(self.len.get());
+            self.len.set(Some(0));
+        }
+        else {
+            self.len.set(None);
+        }
+        split
+    }
+}
+
+impl<T> Default for DoublyLinkedList<T> {
+    fn default() -> DoublyLinkedList<T> {
+        DoublyLinkedList::new()
+    }
+}
+
+// The elements live in the arena, so only the ends can be shown.
+impl<T> fmt::Debug for DoublyLinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoublyLinkedList").
+            field("head", &self.head).
+            field("tail", &self.tail).
+            finish()
+    }
+}
+
+/// Iterator over the elements of a [`DoublyLinkedList`]. Can be run from
+/// either end. Created by DoublyLinkedList::iter().
+pub struct DoublyLinkedListIter<'a, T> {
+    nodes: &'a ListArena<T>,
+    front: Option<ListKey<T>>,
+    back: Option<ListKey<T>>,
+    // Keeps the two ends from passing each other.
+    remaining: usize
+}
+
+impl<'a, T> Iterator for DoublyLinkedListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.nodes[self.front?];
+        self.front = node.next;
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for DoublyLinkedListIter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = &self.nodes[self.back?];
+        self.back = node.prev;
+        self.remaining -= 1;
+        Some(&node.data)
+    }
+}
+
+/// A read-only cursor over a [`DoublyLinkedList`]. Points either at an
+/// element or at a "ghost" position that sits between the back and the front
+/// of the list, so moving past either end wraps around through it.
+pub struct Cursor<'a, T> {
+    list: &'a DoublyLinkedList<T>,
+    nodes: &'a ListArena<T>,
+    // None is the ghost position.
+    current: Option<ListKey<T>>
+}
+
+impl<'a, T> Cursor<'a, T> {
+    /// Returns the element the cursor points at, or None at the ghost
+    /// position.
+    pub fn current(&self) -> Option<&'a T> {
+        self.current.map(|key| &self.nodes[key].data)
+    }
+
+    /// Returns the key of the element the cursor points at, or None at the
+    /// ghost position.
+    pub fn key(&self) -> Option<ListKey<T>> {
+        self.current
+    }
+
+    /// Moves the cursor one element towards the back.
+    pub fn move_next(&mut self) {
+        self.current = self.list.next_of(self.nodes, self.current);
+    }
+
+    /// Moves the cursor one element towards the front.
+    pub fn move_prev(&mut self) {
+        self.current = self.list.prev_of(self.nodes, self.current);
+    }
+
+    /// Returns the element after the cursor without moving it.
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.list.next_of(self.nodes, self.current).map(|key| &self.nodes[key].data)
+    }
+
+    /// Returns the element before the cursor without moving it.
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.list.prev_of(self.nodes, self.current).map(|key| &self.nodes[key].data)
+    }
+
+    /// Moves the cursor n elements towards the back and returns the key it
+    /// ends up at.
+    pub fn nth_key(mut self, n: usize) -> Option<ListKey<T>> {
+        for _ in 0..n {
+            self.move_next();
+        }
+        self.current
+    }
+}
+
+/// A cursor over a [`DoublyLinkedList`] that can edit the list in place. Like
+/// [`Cursor`], it points either at an element or at the "ghost" position
+/// between the back and the front of the list.
+pub struct CursorMut<'a, T> {
+    list: &'a mut DoublyLinkedList<T>,
+    nodes: &'a mut ListArena<T>,
+    // None is the ghost position.
+    current: Option<ListKey<T>>
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Returns the element the cursor points at, or None at the ghost
+    /// position.
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.current.map(|key| &mut self.nodes[key].data)
+    }
+
+    /// Returns the key of the element the cursor points at, or None at the
+    /// ghost position.
+    pub fn key(&self) -> Option<ListKey<T>> {
+        self.current
+    }
+
+    /// Moves the cursor one element towards the back.
+    pub fn move_next(&mut self) {
+        self.current = self.list.next_of(self.nodes, self.current);
+    }
+
+    /// Moves the cursor one element towards the front.
+    pub fn move_prev(&mut self) {
+        self.current = self.list.prev_of(self.nodes, self.current);
+    }
+
+    /// Returns the element after the cursor without moving it.
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        self.list.next_of(self.nodes, self.current).map(|key| &mut self.nodes[key].data)
+    }
+
+    /// Returns the element before the cursor without moving it.
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        self.list.prev_of(self.nodes, self.current).map(|key| &mut self.nodes[key].data)
+    }
+
+    /// Inserts an element after the cursor and returns its key. At the ghost
+    /// position, the element goes to the front of the list.
+    pub fn insert_after(&mut self, data: T) -> ListKey<T> {
+        let next = self.list.next_of(self.nodes, self.current);
+        self.list.link(self.nodes, data, self.current, next)
+    }
+
+    /// Inserts an element before the cursor and returns its key. At the ghost
+    /// position, the element goes to the back of the list.
+    pub fn insert_before(&mut self, data: T) -> ListKey<T> {
+        let prev = self.list.prev_of(self.nodes, self.current);
+        self.list.link(self.nodes, data, prev, self.current)
+    }
+
+    /// Removes and returns the element the cursor points at, and moves the
+    /// cursor on to the next element. Returns None at the ghost position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let key = self.current?;
+        self.current = self.list.next_of(self.nodes, Some(key));
+        self.list.remove(self.nodes, key)
+    }
+
+    /// Moves every element of other in after the cursor (at the front of the
+    /// list at the ghost position) in O(1) time. other has to share the
+    /// list's arena.
+    pub fn splice_after(&mut self, other: DoublyLinkedList<T>) {
+        self.list.splice_after_node(self.nodes, self.current, other);
+    }
+
+    /// Moves every element after the cursor into a new list (the whole list
+    /// at the ghost position) in O(1) time. The new list shares the arena.
+    pub fn split_after(&mut self) -> DoublyLinkedList<T> {
+        self.list.split_after_node(self.nodes, self.current)
+    }
+}
+
+#[cfg(test)]
+mod vec_stack_tests {
+    use super::*;
+
+    // Push some data to the stack. Pop it all off the stack. Verify that all
+    // of the data is returned in the correct order.
+    #[test]
+    fn push_and_pop() {
+        let mut stack = VecStack::new();
+
+        // Fill the stack.
+        for i in 0..10 {
+            stack.push(i);
+        }
+
+        // Empty the stack and make assertions.
+        for i in (0..10).rev() {
+            assert_eq!(i, stack.pop());
+        }
+    }
+
+    // Pop from an empty stack. This is an error on the part of the caller and
+    // should result in a panic.
+    #[test]
+    #[should_panic(expected = "Cannot pop from an empty stack.")]
+    fn pop_from_empty() {
+        let mut stack:VecStack<i32> = VecStack::new();
+        stack.pop();
+    }
+
+    #[test]
+    fn try_pop_and_peek() {
+        let mut stack = VecStack::with_capacity(4);
+        assert!(stack.is_empty());
+        assert_eq!(stack.try_pop(), None);
+        assert_eq!(stack.peek(), None);
+
+        stack.push(String::from("a"));
+        stack.push(String::from("b"));
+        stack.peek_mut().unwrap().push('!');
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.peek().map(|s| s.as_str()), Some("b!"));
+
+        // The stack is iterated from top to bottom.
+        let contents: Vec<&str> = stack.iter().map(|s| s.as_str()).collect();
+        assert_eq!(contents, vec!["b!", "a"]);
+
+        assert_eq!(stack.try_pop().as_deref(), Some("b!"));
+        assert_eq!(stack.try_pop().as_deref(), Some("a"));
+        assert_eq!(stack.try_pop(), None);
+    }
+}
+
+#[cfg(test)]
+mod min_max_stack_tests {
+    use super::*;
+
+    #[test]
+    fn min_and_max() {
+        let mut stack = MinMaxStack::new();
+        assert_eq!(stack.min(), None);
+        assert_eq!(stack.max(), None);
+
+        let values = [5, 3, 8, 3, 1, 9, 2];
+        for (i, value) in values.iter().enumerate() {
+            stack.push(*value);
+            assert_eq!(stack.min(), values[..=i].iter().min());
+            assert_eq!(stack.max(), values[..=i].iter().max());
+        }
+        assert_eq!(stack.len(), values.len());
+        assert_eq!(stack.peek(), Some(&2));
+
+        // Popping restores the minimum and maximum of what's left.
+        for i in (0..values.len()).rev() {
+            assert_eq!(stack.min(), values[..=i].iter().min());
+            assert_eq!(stack.max(), values[..=i].iter().max());
+            assert_eq!(stack.pop(), values[i]);
+        }
+        assert!(stack.is_empty());
+        assert_eq!(stack.try_pop(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut stack = MinMaxStack::with_capacity(3);
+        stack.push(1.5);
+        stack.push(-2.0);
+        stack.push(0.25);
+        let contents: Vec<f64> = stack.iter().copied().collect();
+        assert_eq!(contents, vec![0.25, -2.0, 1.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot pop from an empty stack.")]
+    fn pop_from_empty() {
+        let mut stack: MinMaxStack<i32> = MinMaxStack::new();
+        stack.pop();
+    }
+}
+
+/// A vector-based stack implementation.
+/// Data stored on the stack is owned by the stack. Ownership of data returned
+/// by pop() will be given to the caller of pop().
+// Implementing a queue this way probably wouldn't work but this should be easy.
+// Considering that vec<T> implements its own push and pop() this is trivial...
+pub struct VecStack<T> {
+    // The vector that stores our data.
+    vector: Vec<T>,
+}
+
+impl<T> Default for VecStack<T> {
+    fn default() -> VecStack<T> {
+        VecStack::new()
+    }
+}
+
+impl<T> VecStack<T> {
+    /// Returns a new empty stack.
+    pub fn new() -> VecStack<T> {
+        VecStack {
+            vector: Vec::new()
+        }
+    }
+
+    /// Returns a new empty stack with room for capacity items.
+    pub fn with_capacity(capacity: usize) -> VecStack<T> {
+        VecStack {
+            vector: Vec::with_capacity(capacity)
+        }
+    }
+
+    /// The number of items on the stack.
+    pub fn len(&self) -> usize {
+        self.vector.len()
+    }
+
+    /// Returns true if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.vector.is_empty()
+    }
+
+    /// Puts an item on the top of the stack.
+    pub fn push(&mut self, item: T) {
+        self.vector.push(item);
+    }
+
+    /// Removes and returns the item on the top of the stack. Panics if the
+    /// stack is empty; see try_pop().
     pub fn pop(&mut self) -> T {
         if let Some(item) = self.vector.pop() {
             item
@@ -250,4 +1925,684 @@ impl<T> VecStack<T> {
             panic!("Cannot pop from an empty stack.");
         }
     }
+
+    /// Removes and returns the item on the top of the stack. Returns None if
+    /// the stack is empty.
+    pub fn try_pop(&mut self) -> Option<T> {
+        self.vector.pop()
+    }
+
+    /// The item on the top of the stack.
+    pub fn peek(&self) -> Option<&T> {
+        self.vector.last()
+    }
+
+    /// A mutable reference to the item on the top of the stack.
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.vector.last_mut()
+    }
+
+    /// Get an iterator over the items, from the top of the stack to the
+    /// bottom.
+    pub fn iter(&self) -> std::iter::Rev<std::slice::Iter<'_, T>> {
+        self.vector.iter().rev()
+    }
+}
+
+/// A stack that also reports the smallest and largest items on it in O(1)
+/// time. Items that can't be compared (e.g. NaN) are treated as equal.
+/// There is no peek_mut(), since changing an item could change the minimum
+/// or maximum.
+// Each entry remembers where the minimum and maximum of the entries up to and
+// including it are, so popping an entry uncovers the previous ones.
+pub struct MinMaxStack<T> {
+    entries: Vec<MinMaxEntry<T>>
+}
+
+struct MinMaxEntry<T> {
+    item: T,
+    min: usize,
+    max: usize
+}
+
+impl<T> Default for MinMaxStack<T>
+    where T: PartialOrd
+{
+    fn default() -> MinMaxStack<T> {
+        MinMaxStack::new()
+    }
+}
+
+impl<T> MinMaxStack<T>
+    where T: PartialOrd
+{
+    /// Returns a new empty stack.
+    pub fn new() -> MinMaxStack<T> {
+        MinMaxStack {
+            entries: Vec::new()
+        }
+    }
+
+    /// Returns a new empty stack with room for capacity items.
+    pub fn with_capacity(capacity: usize) -> MinMaxStack<T> {
+        MinMaxStack {
+            entries: Vec::with_capacity(capacity)
+        }
+    }
+
+    /// The number of items on the stack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Puts an item on the top of the stack.
+    pub fn push(&mut self, item: T) {
+        let top = self.entries.len();
+        let (min, max) = match self.entries.last() {
+            Some(last) => (
+                if item < self.entries[last.min].item { top } else { last.min },
+                if item > self.entries[last.max].item { top } else { last.max }
+            ),
+            None => (top, top)
+        };
+        self.entries.push(MinMaxEntry { item, min, max });
+    }
+
+    /// Removes and returns the item on the top of the stack. Panics if the
+    /// stack is empty; see try_pop().
+    pub fn pop(&mut self) -> T {
+        if let Some(item) = self.try_pop() {
+            item
+        }
+        else {
+            panic!("Cannot pop from an empty stack.");
+        }
+    }
+
+    /// Removes and returns the item on the top of the stack. Returns None if
+    /// the stack is empty.
+    pub fn try_pop(&mut self) -> Option<T> {
+        self.entries.pop().map(|entry| entry.item)
+    }
+
+    /// The item on the top of the stack.
+    pub fn peek(&self) -> Option<&T> {
+        self.entries.last().map(|entry| &entry.item)
+    }
+
+    /// The smallest item on the stack.
+    pub fn min(&self) -> Option<&T> {
+        self.entries.last().map(|entry| &self.entries[entry.min].item)
+    }
+
+    /// The largest item on the stack.
+    pub fn max(&self) -> Option<&T> {
+        self.entries.last().map(|entry| &self.entries[entry.max].item)
+    }
+
+    /// Get an iterator over the items, from the top of the stack to the
+    /// bottom.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().rev().map(|entry| &entry.item)
+    }
+}
+
+#[cfg(test)]
+mod deque_tests {
+    use super::*;
+
+    fn contents<T>(deque: &Deque<T>) -> Vec<T>
+        where T: Clone
+    {
+        deque.iter().cloned().collect()
+    }
+
+    #[test]
+    fn push_and_pop_at_both_ends() {
+        let mut deque = Deque::new();
+        assert_eq!(deque.pop_front(), None);
+        assert_eq!(deque.pop_back(), None);
+
+        // Alternate ends, so the contents wrap around while the deque grows.
+        for i in 0..50 {
+            if i % 2 == 0 {
+                deque.push_back(i);
+            }
+            else {
+                deque.push_front(i);
+            }
+        }
+        assert_eq!(deque.len(), 50);
+        assert_eq!(deque.front(), Some(&49));
+        assert_eq!(deque.back(), Some(&48));
+
+        let mut expected: Vec<i32> = (1..50).step_by(2).rev().chain((0..50).step_by(2)).collect();
+        assert_eq!(contents(&deque), expected);
+
+        // Use it as a FIFO queue for a while.
+        for i in 50..100 {
+            assert_eq!(deque.pop_front(), Some(expected.remove(0)));
+            deque.push_back(i);
+            expected.push(i);
+        }
+        assert_eq!(contents(&deque), expected);
+        assert_eq!(deque.pop_back(), Some(99));
+        assert_eq!(deque.len(), 49);
+    }
+
+    #[test]
+    fn indexing() {
+        let mut deque: Deque<String> = ["b", "c"].iter().map(|s| s.to_string()).collect();
+        deque.push_front(String::from("a"));
+        assert_eq!(deque[0], "a");
+        assert_eq!(deque[2], "c");
+        deque[1].push('!');
+        assert_eq!(deque.get(1).map(|s| s.as_str()), Some("b!"));
+        assert_eq!(deque.get(3), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to access an index past the end of a deque.")]
+    fn index_out_of_bounds() {
+        let mut deque = Deque::with_capacity(4);
+        deque.push_back(1);
+        let _ = deque[1];
+    }
+
+    #[test]
+    fn make_contiguous() {
+        // Try every way the contents can be split across the end of the
+        // buffer.
+        for front in 0..8 {
+            let mut deque = Deque::with_capacity(8);
+            for i in 0..(8 - front) {
+                deque.push_back(i);
+            }
+            for i in 1..=front {
+                deque.push_front(-i);
+            }
+            deque.pop_back();
+
+            let expected = contents(&deque);
+            assert_eq!(deque.make_contiguous(), expected.as_slice());
+            assert_eq!(deque.as_slices().1, &[]);
+            assert_eq!(contents(&deque), expected);
+        }
+    }
+
+    #[test]
+    fn drops_items() {
+        let item = Rc::new(0);
+        let mut deque = Deque::new();
+        for _ in 0..10 {
+            deque.push_front(Rc::clone(&item));
+            deque.push_back(Rc::clone(&item));
+        }
+        deque.pop_front();
+        deque.pop_back();
+        assert_eq!(Rc::strong_count(&item), 19);
+        drop(deque);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn zero_sized_types() {
+        let mut deque = Deque::new();
+        deque.push_front(());
+        deque.push_back(());
+        deque.push_front(());
+        assert_eq!(deque.make_contiguous().len(), 3);
+        assert_eq!(deque.pop_back(), Some(()));
+        assert_eq!(deque.len(), 2);
+    }
+}
+
+// Like DynamicArray, a Deque manages the slots of a RawBuf itself. Its items
+// fill the slots from head onwards, wrapping around to the start of the buffer
+// when they reach the end.
+
+/// A double-ended queue. Items can be added and removed at both ends in
+/// amortized O(1) time, so it works as a FIFO queue, a stack or both.
+/// It is backed by a circular buffer that doubles in size when it fills up.
+pub struct Deque<T> {
+    buf: RawBuf<T>,
+    head: usize,
+    len: usize
+}
+
+impl<T> Deque<T> {
+    /// Returns a new empty deque. It doesn't allocate until something is
+    /// added to it.
+    pub fn new() -> Deque<T> {
+        Deque {
+            buf: RawBuf::new(),
+            head: 0,
+            len: 0
+        }
+    }
+
+    /// Returns a new empty deque with room for capacity items.
+    pub fn with_capacity(capacity: usize) -> Deque<T> {
+        let mut deque = Deque::new();
+        deque.buf.resize(capacity);
+        deque
+    }
+
+    /// The number of items in the deque.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of items the deque can hold before it has to grow.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity
+    }
+
+    /// Adds an item to the back of the deque.
+    pub fn push_back(&mut self, item: T) {
+        self.grow_if_full();
+        // SAFETY: the deque isn't full, so the slot after the back is free.
+        unsafe { ptr::write(self.slot(self.len), item) };
+        self.len += 1;
+    }
+
+    /// Adds an item to the front of the deque.
+    pub fn push_front(&mut self, item: T) {
+        self.grow_if_full();
+        self.head = self.wrap_back(self.head);
+        // SAFETY: the deque isn't full, so the slot before the front is free.
+        unsafe { ptr::write(self.slot(0), item) };
+        self.len += 1;
+    }
+
+    /// Removes the item at the back of the deque and returns it. Returns
+    /// None if the deque is empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        // SAFETY: the slot held the back item, which now belongs to nobody but
+        // the caller.
+        Some(unsafe { ptr::read(self.slot(self.len)) })
+    }
+
+    /// Removes the item at the front of the deque and returns it. Returns
+    /// None if the deque is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        // SAFETY: the slot held the front item, which now belongs to nobody
+        // but the caller.
+        let item = unsafe { ptr::read(self.slot(0)) };
+        self.head = self.wrap_add(self.head, 1);
+        self.len -= 1;
+        Some(item)
+    }
+
+    /// The item at the front of the deque.
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// The item at the back of the deque.
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)?)
+    }
+
+    /// The item index places from the front of the deque.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            // SAFETY: the slot holds an item.
+            Some(unsafe { &*self.slot(index) })
+        }
+        else {
+            None
+        }
+    }
+
+    /// A mutable reference to the item index places from the front of the
+    /// deque.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            // SAFETY: the slot holds an item, and self is borrowed mutably.
+            Some(unsafe { &mut *self.slot(index) })
+        }
+        else {
+            None
+        }
+    }
+
+    /// The items of the deque, in order, as two slices: the items up to the
+    /// end of the buffer, then the items that wrapped around to its start.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (front, back) = self.split();
+        // SAFETY: split() returns the lengths of the two runs of items that
+        // start at head and at the start of the buffer.
+        unsafe {
+            (slice::from_raw_parts(self.slot(0), front),
+                slice::from_raw_parts(self.buf.ptr(), back))
+        }
+    }
+
+    /// Moves the items so that they are next to each other in the buffer,
+    /// and returns them as one slice. Takes O(n) time if the items wrapped
+    /// around the end of the buffer, and O(1) time otherwise.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        let (front, back) = self.split();
+        if back > 0 {
+            // The free slots lie between the two runs. Move the back run to
+            // just before the front run, which leaves one run in the wrong
+            // order, then rotate it.
+            let start = self.head - back;
+            // SAFETY: the back run fits in the free slots and the slots of
+            // the back run itself, and ptr::copy allows overlapping ranges.
+            // Afterwards the len slots from start hold the items.
+            unsafe {
+                ptr::copy(self.buf.ptr(), self.buf.ptr().add(start), back);
+                slice::from_raw_parts_mut(self.buf.ptr().add(start), front + back).rotate_left(back);
+            }
+            self.head = start;
+        }
+        // SAFETY: the len slots from head hold the items.
+        unsafe { slice::from_raw_parts_mut(self.slot(0), self.len) }
+    }
+
+    /// Get an iterator over the items, from front to back.
+    pub fn iter(&self) -> std::iter::Chain<slice::Iter<'_, T>, slice::Iter<'_, T>> {
+        let (front, back) = self.as_slices();
+        front.iter().chain(back.iter())
+    }
+
+    /// Removes every item from the deque.
+    pub fn clear(&mut self) {
+        while self.pop_back().is_some() {}
+        self.head = 0;
+    }
+
+    // Returns a pointer to the slot of the item index places from the front.
+    // Doesn't check that the slot holds an item.
+    fn slot(&self, index: usize) -> *mut T {
+        // SAFETY: wrap_add() returns a slot inside the buffer.
+        unsafe { self.buf.ptr().add(self.wrap_add(self.head, index)) }
+    }
+
+    // Returns the slot i places after slot, wrapping around the end of the
+    // buffer. i must be less than the capacity.
+    fn wrap_add(&self, slot: usize, i: usize) -> usize {
+        // Written to avoid overflow, since zero-sized types have a capacity
+        // of usize::MAX.
+        let to_end = self.buf.capacity - slot;
+        if i >= to_end { i - to_end } else { slot + i }
+    }
+
+    // Returns the slot before slot, wrapping around the start of the buffer.
+    fn wrap_back(&self, slot: usize) -> usize {
+        if slot == 0 { self.buf.capacity - 1 } else { slot - 1 }
+    }
+
+    // Returns the number of items from head to the end of the buffer, and
+    // the number that wrapped around to its start.
+    fn split(&self) -> (usize, usize) {
+        let to_end = self.buf.capacity - self.head;
+        if self.len > to_end {
+            (to_end, self.len - to_end)
+        }
+        else {
+            (self.len, 0)
+        }
+    }
+
+    // Doubles the capacity if the deque is full. The buffer's new slots come
+    // after the old ones, so if the items wrapped around, the front run is
+    // moved to the end of the new buffer.
+    fn grow_if_full(&mut self) {
+        if self.len < self.buf.capacity {
+            return;
+        }
+        if mem::size_of::<T>() == 0 {
+            panic!("A deque cannot hold more than usize::MAX items.");
+        }
+
+        let old_capacity = self.buf.capacity;
+        let (front, back) = self.split();
+        self.buf.resize((old_capacity * 2).max(4));
+        if back > 0 {
+            let new_head = self.buf.capacity - front;
+            // SAFETY: the front run moves into the new slots at the end of
+            // the buffer, which ptr::copy allows to overlap its old ones.
+            unsafe { ptr::copy(self.buf.ptr().add(self.head), self.buf.ptr().add(new_head), front) };
+            self.head = new_head;
+        }
+    }
+}
+
+impl<T> Drop for Deque<T> {
+    fn drop(&mut self) {
+        let (front, back) = self.split();
+        // SAFETY: these are the two runs of items. The buffer frees itself
+        // afterwards.
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.slot(0), front));
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.buf.ptr(), back));
+        }
+    }
+}
+
+impl<T> Index<usize> for Deque<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(item) => item,
+            None => panic!("Attempted to access an index past the end of a deque.")
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Deque<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        match self.get_mut(index) {
+            Some(item) => item,
+            None => panic!("Attempted to access an index past the end of a deque.")
+        }
+    }
+}
+
+impl<T> Default for Deque<T> {
+    fn default() -> Deque<T> {
+        Deque::new()
+    }
+}
+
+impl<T> fmt::Debug for Deque<T>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Items are added to the back.
+impl<T> Extend<T> for Deque<T> {
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        for item in iter {
+            self.push_back(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for Deque<T> {
+    fn from_iter<I>(iter: I) -> Deque<T>
+        where I: IntoIterator<Item = T>
+    {
+        let mut deque = Deque::new();
+        deque.extend(iter);
+        deque
+    }
+}
+
+#[cfg(test)]
+mod bounded_queue_tests {
+    use super::*;
+
+    #[test]
+    fn overwrite_when_full() {
+        let mut queue = BoundedQueue::new(3, OverflowPolicy::Overwrite);
+        for i in 0..3 {
+            assert_eq!(queue.push(i), None);
+        }
+        assert!(queue.is_full());
+
+        // The oldest items make way for new ones.
+        assert_eq!(queue.push(3), Some(0));
+        assert_eq!(queue.push(4), Some(1));
+        let contents: Vec<i32> = queue.iter().copied().collect();
+        assert_eq!(contents, vec![2, 3, 4]);
+
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.push(5), None);
+        assert_eq!(queue.front(), Some(&3));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.capacity(), 3);
+    }
+
+    #[test]
+    fn reject_when_full() {
+        let mut queue = BoundedQueue::new(2, OverflowPolicy::Reject);
+        assert_eq!(queue.push("a"), None);
+        assert_eq!(queue.push("b"), None);
+
+        // New items are handed back until there is room.
+        assert_eq!(queue.push("c"), Some("c"));
+        assert_eq!(queue.pop(), Some("a"));
+        assert_eq!(queue.push("d"), None);
+        let contents: Vec<&str> = queue.iter().copied().collect();
+        assert_eq!(contents, vec!["b", "d"]);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut overwrite = BoundedQueue::new(0, OverflowPolicy::Overwrite);
+        let mut reject = BoundedQueue::new(0, OverflowPolicy::Reject);
+        assert_eq!(overwrite.push(1), Some(1));
+        assert_eq!(reject.push(1), Some(1));
+        assert!(overwrite.is_empty() && reject.is_empty());
+    }
+}
+
+/// What a [`BoundedQueue`] does with a new item when it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest item to make room for the new one.
+    Overwrite,
+    /// Turn the new item away.
+    Reject
+}
+
+/// A FIFO queue that never holds more than a fixed number of items, and
+/// never allocates after it is created. When it is full, new items are
+/// handled by its [`OverflowPolicy`].
+pub struct BoundedQueue<T> {
+    deque: Deque<T>,
+    capacity: usize,
+    policy: OverflowPolicy
+}
+
+impl<T> BoundedQueue<T> {
+    /// Returns a new empty queue that holds up to capacity items.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> BoundedQueue<T> {
+        BoundedQueue {
+            deque: Deque::with_capacity(capacity),
+            capacity,
+            policy
+        }
+    }
+
+    /// The number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.deque.len()
+    }
+
+    /// Returns true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.deque.is_empty()
+    }
+
+    /// Returns true if the queue holds as many items as it can.
+    pub fn is_full(&self) -> bool {
+        self.deque.len() == self.capacity
+    }
+
+    /// The most items the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// What the queue does with new items when it is full.
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    /// Adds an item to the back of the queue. If the queue is full, returns
+    /// the item that didn't fit: the oldest item if the policy is Overwrite,
+    /// or the new one if it is Reject. Returns None otherwise.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if !self.is_full() {
+            self.deque.push_back(item);
+            return None;
+        }
+
+        match self.policy {
+            OverflowPolicy::Overwrite if self.capacity > 0 => {
+                let oldest = self.deque.pop_front();
+                self.deque.push_back(item);
+                oldest
+            },
+            _ => Some(item)
+        }
+    }
+
+    /// Removes the item at the front of the queue (the oldest one) and
+    /// returns it. Returns None if the queue is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        self.deque.pop_front()
+    }
+
+    /// The item at the front of the queue.
+    pub fn front(&self) -> Option<&T> {
+        self.deque.front()
+    }
+
+    /// Get an iterator over the items, from oldest to newest.
+    pub fn iter(&self) -> std::iter::Chain<slice::Iter<'_, T>, slice::Iter<'_, T>> {
+        self.deque.iter()
+    }
+}
+
+impl<T> fmt::Debug for BoundedQueue<T>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedQueue").
+            field("items", &self.deque).
+            field("capacity", &self.capacity).
+            field("policy", &self.policy).
+            finish()
+    }
 }
@@ -0,0 +1,353 @@
+use std::error::Error;
+use std::fmt;
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    // Encode a value, decode it again and make sure nothing changed.
+    fn round_trip<T>(value: T)
+        where T: Encode + Decode + PartialEq + fmt::Debug
+    {
+        let bytes = to_bytes(&value);
+        assert_eq!(from_bytes::<T>(&bytes), Ok(value));
+    }
+
+    #[test]
+    fn primitives() {
+        round_trip(0u8);
+        round_trip(u16::MAX);
+        round_trip(123_456u32);
+        round_trip(u64::MAX);
+        round_trip(usize::MAX);
+        round_trip(-1i8);
+        round_trip(i16::MIN);
+        round_trip(-123_456i32);
+        round_trip(i64::MIN);
+        round_trip(isize::MAX);
+        round_trip(true);
+        round_trip(false);
+        round_trip(-0.5f32);
+        round_trip(std::f64::consts::PI);
+        round_trip('ß');
+    }
+
+    #[test]
+    fn compound() {
+        round_trip(String::from("spork"));
+        round_trip(String::new());
+        round_trip(Some(7i32));
+        round_trip(None::<i32>);
+        round_trip(vec![String::from("a"), String::from("bc")]);
+        round_trip((1u8, String::from("one")));
+        round_trip(vec![(1u64, Some(-1i64)), (2, None)]);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let bytes = to_bytes(&String::from("spork"));
+        for len in 0..bytes.len() {
+            assert_eq!(from_bytes::<String>(&bytes[..len]), Err(DecodeError::UnexpectedEnd));
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(matches!(from_bytes::<bool>(&[2]), Err(DecodeError::InvalidData(_))));
+        assert!(matches!(from_bytes::<Option<u8>>(&[2, 0]), Err(DecodeError::InvalidData(_))));
+        assert!(matches!(from_bytes::<String>(&[1, 0, 0, 0, 0, 0, 0, 0, 0xff]),
+            Err(DecodeError::InvalidData(_))));
+        assert!(matches!(from_bytes::<char>(&0xd800u32.to_le_bytes()),
+            Err(DecodeError::InvalidData(_))));
+        assert_eq!(from_bytes::<u8>(&[1, 2]), Err(DecodeError::TrailingBytes(1)));
+    }
+}
+
+/// A value that can be written to the crate's binary format.
+/// Multi-byte numbers are written in little-endian order and lengths as 64-bit
+/// integers, so the output doesn't depend on the machine that wrote it.
+pub trait Encode {
+    /// Append the encoded value to out.
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// A value that can be read back from the crate's binary format.
+pub trait Decode: Sized {
+    /// Read a value from the front of input and advance input past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+/// The reasons decoding can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// The input doesn't start with the expected format identifier.
+    WrongFormat,
+    /// The input was written by a version of the format this code can't read.
+    UnsupportedVersion(u8),
+    /// The input is well-formed but describes an impossible value.
+    InvalidData(String),
+    /// A value was decoded but this many bytes were left over.
+    TrailingBytes(usize)
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "the input ended unexpectedly"),
+            DecodeError::WrongFormat => write!(f, "the input is not in the expected format"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::InvalidData(reason) => write!(f, "invalid data: {}", reason),
+            DecodeError::TrailingBytes(n) => write!(f, "{} bytes were left over after decoding", n)
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Encode a value into a new byte vector.
+pub fn to_bytes<T>(value: &T) -> Vec<u8>
+    where T: Encode + ?Sized
+{
+    let mut out = Vec::new();
+    value.encode(&mut out);
+    out
+}
+
+/// Decode a value that takes up the whole of bytes.
+pub fn from_bytes<T>(mut bytes: &[u8]) -> Result<T, DecodeError>
+    where T: Decode
+{
+    let value = T::decode(&mut bytes)?;
+    if bytes.is_empty() {
+        Ok(value)
+    }
+    else {
+        Err(DecodeError::TrailingBytes(bytes.len()))
+    }
+}
+
+/// Remove the first len bytes from input and return them.
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Ok(taken)
+}
+
+/// Write the identifier and version that start a top-level format (e.g. an
+/// [`Arena`](crate::arena::Arena) checkpoint).
+pub fn encode_header(out: &mut Vec<u8>, magic: &[u8; 4], version: u8) {
+    out.extend_from_slice(magic);
+    out.push(version);
+}
+
+/// Read the header written by encode_header() and return the version.
+/// Fails if the identifier doesn't match or the version is newer than
+/// max_version.
+pub fn decode_header(input: &mut &[u8], magic: &[u8; 4], max_version: u8)
+    -> Result<u8, DecodeError>
+{
+    if take(input, 4)? != magic {
+        return Err(DecodeError::WrongFormat);
+    }
+    let version = u8::decode(input)?;
+    if version == 0 || version > max_version {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    Ok(version)
+}
+
+// Fixed-width numbers are written in little-endian order.
+macro_rules! impl_for_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+                    let bytes = take(input, std::mem::size_of::<$t>())?;
+                    // take() returned exactly the number of bytes asked for.
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_for_numbers!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+// usize and isize are always written as 64 bits so the format is the same on
+// every platform.
+impl Encode for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+}
+
+impl Decode for usize {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        usize::try_from(u64::decode(input)?).
+            map_err(|_| DecodeError::InvalidData(String::from("length does not fit in usize")))
+    }
+}
+
+impl Encode for isize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as i64).encode(out);
+    }
+}
+
+impl Decode for isize {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        isize::try_from(i64::decode(input)?).
+            map_err(|_| DecodeError::InvalidData(String::from("value does not fit in isize")))
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(DecodeError::InvalidData(format!("{} is not a bool", b)))
+        }
+    }
+}
+
+impl Encode for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out);
+    }
+}
+
+impl Decode for char {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let code = u32::decode(input)?;
+        char::from_u32(code).
+            ok_or_else(|| DecodeError::InvalidData(format!("{:#x} is not a char", code)))
+    }
+}
+
+// Strings are written as a length followed by UTF-8 bytes.
+impl Encode for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out);
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = usize::decode(input)?;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec()).
+            map_err(|_| DecodeError::InvalidData(String::from("string is not valid UTF-8")))
+    }
+}
+
+// Options are written as a 0 (None) or 1 (Some) tag, followed by the value.
+impl<T> Encode for Option<T>
+    where T: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            },
+            None => out.push(0)
+        }
+    }
+}
+
+impl<T> Decode for Option<T>
+    where T: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            b => Err(DecodeError::InvalidData(format!("{} is not an Option tag", b)))
+        }
+    }
+}
+
+// Sequences are written as a length followed by each element.
+impl<T> Encode for [T]
+    where T: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T> Encode for Vec<T>
+    where T: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode(out);
+    }
+}
+
+impl<T> Decode for Vec<T>
+    where T: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let len = usize::decode(input)?;
+        // Don't trust the length to size the allocation: a corrupted length
+        // would otherwise ask for far more memory than the input could fill.
+        let mut v = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            v.push(T::decode(input)?);
+        }
+        Ok(v)
+    }
+}
+
+impl<T> Encode for &T
+    where T: Encode + ?Sized
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+}
+
+impl<A, B> Encode for (A, B)
+    where A: Encode, B: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A, B> Decode for (A, B)
+    where A: Decode, B: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
@@ -1,6 +1,11 @@
+use crate::arena::{Arena, Key};
+use std::cmp::Ordering;
+use std::fmt;
+
 #[cfg(test)]
 mod heap_tests {
     use super::*;
+    use std::cmp::Reverse;
 
     // Extracting from an empty heap should return None.
     #[test]
@@ -49,6 +54,158 @@ mod heap_tests {
         assert_descending_order(&mut max_heap);
     }
 
+    #[test]
+    fn with_comparator() {
+        // Longest string first, ties broken alphabetically.
+        let mut heap = Heap::with_comparator(|a: &&str, b: &&str| {
+            b.len().cmp(&a.len()).then(a.cmp(b))
+        });
+        for s in ["bb", "a", "ccc", "aa", "b"] {
+            heap.insert(s);
+        }
+        let mut order = Vec::new();
+        while let Some(s) = heap.extract() {
+            order.push(s);
+        }
+        assert_eq!(order, vec!["ccc", "aa", "bb", "a", "b"]);
+    }
+
+    #[test]
+    fn by_key() {
+        struct Job {
+            name: &'static str,
+            deadline: u32
+        }
+
+        // Earliest deadline first.
+        let mut heap = Heap::by_key(|job: &Job| job.deadline);
+        heap.insert(Job { name: "report", deadline: 5 });
+        heap.insert(Job { name: "email", deadline: 1 });
+        heap.insert(Job { name: "review", deadline: 3 });
+        assert_eq!(heap.extract().unwrap().name, "email");
+        assert_eq!(heap.extract().unwrap().name, "review");
+        assert_eq!(heap.extract().unwrap().name, "report");
+        assert!(heap.extract().is_none());
+
+        // Reverse the key to get the largest first, here on a tuple's second
+        // field.
+        let mut heap = Heap::by_key(|pair: &(char, i32)| Reverse(pair.1));
+        for pair in [('a', 2), ('b', 7), ('c', -1)] {
+            heap.insert(pair);
+        }
+        assert_eq!(heap.extract(), Some(('b', 7)));
+        assert_eq!(heap.extract(), Some(('a', 2)));
+        assert_eq!(heap.extract(), Some(('c', -1)));
+    }
+
+    #[test]
+    fn from_vec() {
+        let v: Vec<i32> = (0..1000).map(|i| (i * 7919) % 1000).collect();
+        let heap = Heap::from_vec(v.clone(), true);
+        assert_eq!(heap.len(), 1000);
+        assert_eq!(heap.peek(), Some(&999));
+        assert_eq!(heap.into_sorted_vec(), (0..1000).rev().collect::<Vec<_>>());
+
+        // Building the heap should take a linear number of comparisons.
+        let comparisons = std::cell::Cell::new(0);
+        let heap = Heap::from_vec_with_comparator(v, |a: &i32, b: &i32| {
+            comparisons.set(comparisons.get() + 1);
+            a.cmp(b)
+        });
+        assert!(comparisons.get() <= 2 * 1000);
+        assert_eq!(heap.into_sorted_vec(), (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn arity() {
+        fn sorted<const D: usize>(heap: Heap<i32, NaturalOrder, D>) -> Vec<i32> {
+            heap.into_sorted_vec()
+        }
+
+        let v: Vec<i32> = (0..500).map(|i| (i * 7919) % 500).collect();
+        let ascending: Vec<i32> = (0..500).collect();
+        let heap = Heap::from_vec(v.clone(), false);
+        assert_eq!(sorted(heap.into_arity::<3>()), ascending);
+
+        let mut heap = Heap::new(false).into_arity::<4>();
+        for i in v.iter() {
+            heap.insert(*i);
+        }
+        assert_eq!(sorted(heap), ascending);
+
+        let mut heap = Heap::with_arity::<5>(|a: &i32, b: &i32| b.cmp(a));
+        heap.extend(v.iter().copied());
+        assert_eq!(heap.extract(), Some(499));
+        assert_eq!(heap.len(), 499);
+
+        let heap: Heap<i32, NaturalOrder, 8> = v.iter().copied().collect();
+        assert_eq!(heap.peek(), Some(&0));
+        let mut heap = heap.into_arity::<16>();
+        *heap.peek_mut().unwrap() = 1000;
+        assert_eq!(heap.drain_sorted().last(), Some(1000));
+    }
+
+    #[test]
+    fn meld() {
+        let mut a = Heap::from_vec(vec![5, 1, 9], false);
+        let b = Heap::from_vec(vec![4, 0, 7, 2], false);
+        PriorityQueue::meld(&mut a, b);
+        assert_eq!(PriorityQueue::len(&a), 7);
+        assert_eq!(a.into_sorted_vec(), vec![0, 1, 2, 4, 5, 7, 9]);
+    }
+
+    #[test]
+    fn collection_traits() {
+        let mut heap: Heap<i32> = [5, 1, 4].into_iter().collect();
+        assert_eq!(heap.peek(), Some(&1));
+        heap.extend([3, 0]);
+        assert_eq!(heap.peek(), Some(&0));
+        heap.extend(10..20);
+        assert_eq!(heap.len(), 15);
+        assert_eq!((&heap).into_iter().count(), 15);
+
+        let mut unordered: Vec<i32> = heap.into_iter().collect();
+        unordered.sort();
+        let mut expected = vec![0, 1, 3, 4, 5];
+        expected.extend(10..20);
+        assert_eq!(unordered, expected);
+
+        let empty: Heap<i32> = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        assert!(empty.peek().is_none());
+    }
+
+    #[test]
+    fn drain_sorted() {
+        let mut heap = Heap::from_vec(vec![3, 1, 2, 5, 4], false);
+        assert_eq!(heap.drain_sorted().take(2).collect::<Vec<_>>(), vec![1, 2]);
+        // The rest were dropped along with the iterator.
+        assert!(heap.is_empty());
+
+        heap.extend([3, 1, 2]);
+        assert_eq!(heap.drain_sorted().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn peek_mut() {
+        let mut heap = Heap::from_vec(vec![1, 2, 3, 4], false);
+        assert!(Heap::<i32>::new(false).peek_mut().is_none());
+
+        // Changing the first element moves it to its new place.
+        *heap.peek_mut().unwrap() = 10;
+        assert_eq!(heap.peek(), Some(&2));
+        {
+            let mut first = heap.peek_mut().unwrap();
+            *first -= 5;
+        }
+        assert_eq!(heap.peek(), Some(&-3));
+
+        let mut first = heap.peek_mut().unwrap();
+        *first = 7;
+        assert_eq!(PeekMut::pop(first), 3);
+        assert_eq!(heap.into_sorted_vec(), vec![4, 7, 10]);
+    }
+
     // Extracting everything from the min heap should result in ascending order.
     fn assert_ascending_order(min_heap: &mut Heap<i32>) {
         let expect_message = "Failed to extract all inserted elements from the heap.";
@@ -74,10 +231,124 @@ mod heap_tests {
     }
 }
 
-/// A heap. Can be configured as a min-heap or a max-heap when constructed.
-#[derive(Debug)]
-pub struct Heap<T> {
-    is_max_heap: bool,
+/// Decides the order elements come out of a [`Heap`].
+/// Implemented for closures of the form |a: &T, b: &T| -> Ordering.
+pub trait Comparator<T> {
+    /// Returns Less if a should be extracted before b, Greater if b should be
+    /// extracted first, and Equal if it doesn't matter.
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+
+    /// Returns true only if other is known to put elements in the same order
+    /// as this comparator. The mergeable heaps only meld by linking their
+    /// trees together when this holds. The default is false, since two
+    /// closures of the same type can still order differently through what
+    /// they capture, so melding heaps ordered by closures moves the elements
+    /// one at a time.
+    fn same_order(&self, _other: &Self) -> bool
+        where Self: Sized
+    {
+        false
+    }
+}
+
+impl<T, F> Comparator<T> for F
+    where F: Fn(&T, &T) -> Ordering
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// The operations shared by the crate's priority queues: [`Heap`] and the
+/// heaps in [`mergeable_heaps`](crate::mergeable_heaps). The element that
+/// comes first in the queue's order is the one peek() returns and extract()
+/// removes.
+pub trait PriorityQueue<T> {
+    /// Inserts an item into the queue.
+    fn insert(&mut self, item: T);
+
+    /// Removes the element that comes first and returns it. Returns None if
+    /// the queue is empty.
+    fn extract(&mut self) -> Option<T>;
+
+    /// Returns the element that comes first, without removing it.
+    fn peek(&self) -> Option<&T>;
+
+    /// The number of elements in the queue.
+    fn len(&self) -> usize;
+
+    /// Returns true if the queue is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Moves every element of other into this queue, which keeps its own
+    /// ordering.
+    fn meld(&mut self, other: Self)
+        where Self: Sized;
+}
+
+/// Orders elements by their PartialOrd implementation, either smallest first
+/// (a min-heap) or largest first (a max-heap). Elements that can't be compared
+/// (e.g. NaN) are treated as equal.
+/// The default is a min-heap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NaturalOrder {
+    is_max_heap: bool
+}
+
+impl NaturalOrder {
+    /// Largest first if is_max_heap is true, smallest first otherwise.
+    pub fn new(is_max_heap: bool) -> NaturalOrder {
+        NaturalOrder { is_max_heap }
+    }
+}
+
+impl<T> Comparator<T> for NaturalOrder
+    where T: PartialOrd
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+        if self.is_max_heap {
+            ordering.reverse()
+        }
+        else {
+            ordering
+        }
+    }
+
+    fn same_order(&self, other: &NaturalOrder) -> bool {
+        self.is_max_heap == other.is_max_heap
+    }
+}
+
+/// Orders elements by a key computed from each one, smallest key first.
+/// Wrap the key in std::cmp::Reverse to get the largest first.
+/// Created by Heap::by_key().
+#[derive(Clone, Copy)]
+pub struct ByKey<F> {
+    key: F
+}
+
+impl<T, K, F> Comparator<T> for ByKey<F>
+    where F: Fn(&T) -> K, K: PartialOrd
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.key)(a).partial_cmp(&(self.key)(b)).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// A heap. The order elements are extracted in is decided by a
+/// [`Comparator`]: new() builds a plain min-heap or max-heap, while
+/// with_comparator() and by_key() take any ordering.
+/// Each element has up to D children. The constructors build a binary heap
+/// (D = 2), except with_arity(), which takes any arity, and into_arity()
+/// converts a heap to another one. A wider heap is shallower, so inserting is
+/// cheaper, while extracting compares more children at each level.
+/// Elements the comparator considers equal come out in no particular order;
+/// use a [`StableHeap`] to get them in insertion order.
+pub struct Heap<T, C = NaturalOrder, const D: usize = 2> {
+    comparator: C,
     vector: Vec<T>
 }
 
@@ -87,37 +358,140 @@ impl<T> Heap<T>
     /// Creates a new empty heap. If the is_max_heap argument is true, this will
     /// be a max-heap. If the argument is false, it will be a min-heap.
     pub fn new(is_max_heap: bool) -> Heap<T> {
+        Heap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+
+    /// Turns a vector into a min-heap or max-heap (see new()) in O(n) time,
+    /// which is faster than inserting the elements one at a time.
+    pub fn from_vec(vector: Vec<T>, is_max_heap: bool) -> Heap<T> {
+        Heap::from_vec_with_comparator(vector, NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, F> Heap<T, ByKey<F>> {
+    /// Creates a new empty heap that extracts the element with the smallest
+    /// key first, where key(element) computes an element's key.
+    pub fn by_key<K>(key: F) -> Heap<T, ByKey<F>>
+        where F: Fn(&T) -> K, K: PartialOrd
+    {
+        Heap::with_comparator(ByKey { key })
+    }
+}
+
+impl<T, C> Heap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap that extracts elements in the order given by
+    /// the comparator (see [`Comparator`]).
+    pub fn with_comparator(comparator: C) -> Heap<T, C> {
+        Heap {
+            comparator,
+            vector: Vec::new()
+        }
+    }
+
+    /// Turns a vector into a heap ordered by the comparator in O(n) time,
+    /// which is faster than inserting the elements one at a time.
+    pub fn from_vec_with_comparator(vector: Vec<T>, comparator: C) -> Heap<T, C> {
+        let mut heap = Heap { comparator, vector };
+        heap.heapify();
+        heap
+    }
+
+    /// Creates a new empty heap like with_comparator(), but with E children
+    /// per element, e.g. `Heap::with_arity::<4>(NaturalOrder::new(false))`.
+    /// E must be at least 2.
+    pub fn with_arity<const E: usize>(comparator: C) -> Heap<T, C, E> {
         Heap {
-            is_max_heap,
+            comparator,
             vector: Vec::new()
         }
     }
+}
+
+// The constructors above only build binary heaps, so that the arity doesn't
+// have to be spelled out wherever a heap is created.
+impl<T, C, const D: usize> Heap<T, C, D>
+    where C: Comparator<T>
+{
+    /// Rebuilds the heap with E children per element, in O(n) time.
+    /// E must be at least 2.
+    pub fn into_arity<const E: usize>(self) -> Heap<T, C, E> {
+        let mut heap = Heap {
+            comparator: self.comparator,
+            vector: self.vector
+        };
+        heap.heapify();
+        heap
+    }
+
+    /// The number of elements in the heap.
+    pub fn len(&self) -> usize {
+        self.vector.len()
+    }
+
+    /// Returns true if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.vector.is_empty()
+    }
+
+    /// Returns the element that will be extracted next, without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.vector.first()
+    }
+
+    /// Returns a mutable reference to the element that will be extracted
+    /// next. If the element is changed, it is moved to its new place in the
+    /// heap when the returned guard is dropped.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, C, D>> {
+        if self.vector.is_empty() {
+            None
+        }
+        else {
+            Some(PeekMut { heap: self })
+        }
+    }
+
+    /// Get an iterator over the elements of the heap, in no particular
+    /// order.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.vector.iter()
+    }
+
+    /// Get an iterator that extracts the elements of the heap in order. If
+    /// the iterator is dropped before it is exhausted, the remaining elements
+    /// are dropped with it.
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, T, C, D> {
+        DrainSorted { heap: self }
+    }
+
+    /// Returns the elements of the heap in the order they would be
+    /// extracted in.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut sorted = Vec::with_capacity(self.vector.len());
+        while let Some(item) = self.extract() {
+            sorted.push(item);
+        }
+        sorted
+    }
 
     /// Inserts an item into the heap.
     // Puts item at the end of the vector (the bottom-most, rightmost leaf of
-    // the heap). If item dominates its parent, moves item up recursively.
+    // the heap) and moves it up while it dominates its parent.
     pub fn insert(&mut self, item: T) {
         self.vector.push(item);
         self.bubble_up(self.vector.len() - 1);
     }
 
-    // Recursively moves the item at index i up the heap until it is dominated
-    // by its parent.
+    // Moves the item at index i up the heap until it is dominated by its
+    // parent.
     fn bubble_up(&mut self, i: usize) {
-        // If the current node is already the root, we're done.
-        if i > 0 {
-            let p = Heap::<T>::parent(i);
-            
-            if self.dominates(i, p) {
-                self.vector.swap(i, p);
-                self.bubble_up(p);
-            }
-        }
+        sift_up::<T, C, D>(&mut self.vector, i, &self.comparator);
     }
 
-
-    /// Removes the minimum or maximum value, depending on whether this is a
-    /// min or max heap, and returns it. Returns None if the heap is empty.
+    /// Removes the element that comes first in the heap's order (e.g. the
+    /// minimum value of a min-heap) and returns it. Returns None if the heap
+    /// is empty.
     // Gets the first element in the vector and removes it. If there is
     // more than one element in the vector, the last element is moved to
     // the beginning of the vector.
@@ -133,67 +507,1374 @@ impl<T> Heap<T>
         }
     }
 
-    // Starting from index i, recursively swap the current value with its most
-    // dominant child. In the case where the value at i dominates both of its
-    // children, we're done (the recursion bottoms out).
+    // Moves the item at index i down the heap until it dominates all of its
+    // children.
     fn bubble_down(&mut self, i: usize) {
-        // If there is nothing in the heap we're done.
-        // If the current node has no children, we're done.
-        if Heap::<T>::left_child(i) < self.vector.len() {
-            // Get the index of the most dominant element among the current
-            // element and its (at most) two children.
-            let mut i_dom = i;
-            i_dom = self.get_dominant(Heap::<T>::left_child(i), i_dom);
-            if Heap::<T>::right_child(i) < self.vector.len() {
-                i_dom = self.get_dominant(Heap::<T>::right_child(i), i_dom);
-            }
+        sift_down::<T, C, D>(&mut self.vector, i, &self.comparator);
+    }
+
+    // Restore the heap property over the whole vector.
+    fn heapify(&mut self) {
+        heapify::<T, C, D>(&mut self.vector, &self.comparator);
+    }
+}
+
+// The functions below work on any slice laid out as a D-ary heap, so that
+// heapsort can sort a slice in place with the same code Heap uses. They loop
+// rather than recurse, so a deep heap can't overflow the stack.
+
+// Returns the index of the first of the D children of the element at index
+// i. The rest follow it. Does not check whether the child exists.
+fn first_child<const D: usize>(i: usize) -> usize {
+    const { assert!(D >= 2, "A heap's arity must be at least 2.") };
+    (D * i) + 1
+}
+
+// Returns the index of the parent of the element at index i.
+fn parent<const D: usize>(i: usize) -> usize {
+    const { assert!(D >= 2, "A heap's arity must be at least 2.") };
+    if i < 1 {
+        panic!("The root cannot have a parent.");
+    }
+    else {
+        (i - 1) / D
+    }
+}
+
+// Moves the element at index i of v towards the root while it comes out
+// before its parent.
+pub(crate) fn sift_up<T, C, const D: usize>(v: &mut [T], mut i: usize, comparator: &C)
+    where C: Comparator<T>
+{
+    while i > 0 {
+        let p = parent::<D>(i);
+        if comparator.compare(&v[i], &v[p]) != Ordering::Less {
+            break;
+        }
+        v.swap(i, p);
+        i = p;
+    }
+}
+
+// Moves the element at index i of v towards the leaves, swapping it with its
+// most dominant child, until it comes out before all of its children.
+pub(crate) fn sift_down<T, C, const D: usize>(v: &mut [T], mut i: usize, comparator: &C)
+    where C: Comparator<T>
+{
+    loop {
+        let first = first_child::<D>(i);
+        if first >= v.len() {
+            return;
+        }
 
-            // If the current item dominates its children, we're done.
-            if i_dom != i {
-                self.vector.swap(i, i_dom);
-                self.bubble_down(i_dom);
+        // Get the index of the most dominant element among the current
+        // element and its (at most) D children.
+        let mut i_dom = i;
+        let end = first.saturating_add(D).min(v.len());
+        for child in first..end {
+            if comparator.compare(&v[child], &v[i_dom]) == Ordering::Less {
+                i_dom = child;
             }
         }
+
+        // If the current element dominates its children, we're done.
+        if i_dom == i {
+            return;
+        }
+        v.swap(i, i_dom);
+        i = i_dom;
+    }
+}
+
+// Restores the heap property over all of v, working from the last parent back
+// to the root. Most elements are near the bottom and only move a short
+// distance, which makes this O(n) overall.
+pub(crate) fn heapify<T, C, const D: usize>(v: &mut [T], comparator: &C)
+    where C: Comparator<T>
+{
+    if v.len() > 1 {
+        let last_parent = parent::<D>(v.len() - 1);
+        for i in (0..=last_parent).rev() {
+            sift_down::<T, C, D>(v, i, comparator);
+        }
+    }
+}
+
+// The comparator is left out, since closures can't be printed.
+impl<T, C, const D: usize> fmt::Debug for Heap<T, C, D>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Heap").field("vector", &self.vector).finish()
+    }
+}
+
+/// Melding takes O(n + m) time, since the two vectors have to be combined and
+/// re-heapified. The heaps in [`mergeable_heaps`](crate::mergeable_heaps) do
+/// it faster.
+impl<T, C, const D: usize> PriorityQueue<T> for Heap<T, C, D>
+    where C: Comparator<T>
+{
+    fn insert(&mut self, item: T) {
+        Heap::insert(self, item);
     }
 
-    // Returns the index of the left child of the element at index i.
-    // Does not check whether the child exists.
-    fn left_child(i: usize) -> usize {
-        (2 * i) + 1
+    fn extract(&mut self) -> Option<T> {
+        Heap::extract(self)
     }
 
-    // Returns the index of the right child of the element at index i.
-    // Does not check whether the child exists.
-    fn right_child(i: usize) -> usize {
-        (2 * i) + 2
+    fn peek(&self) -> Option<&T> {
+        Heap::peek(self)
     }
 
-    // Returns the index of the parent of the element at index i.
-    fn parent(i: usize) -> usize {
-        if i < 1 {
-            panic!("The root cannot have a parent.");
+    fn len(&self) -> usize {
+        Heap::len(self)
+    }
+
+    fn meld(&mut self, other: Heap<T, C, D>) {
+        self.extend(other.vector);
+    }
+}
+
+/// Builds the heap in O(n) time. The comparator is its default (a min-heap
+/// for [`NaturalOrder`]).
+impl<T, C, const D: usize> FromIterator<T> for Heap<T, C, D>
+    where C: Comparator<T> + Default
+{
+    fn from_iter<I>(iter: I) -> Heap<T, C, D>
+        where I: IntoIterator<Item = T>
+    {
+        let mut heap = Heap {
+            comparator: C::default(),
+            vector: iter.into_iter().collect()
+        };
+        heap.heapify();
+        heap
+    }
+}
+
+impl<T, C, const D: usize> Extend<T> for Heap<T, C, D>
+    where C: Comparator<T>
+{
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        let old_len = self.vector.len();
+        self.vector.extend(iter);
+        let added = self.vector.len() - old_len;
+        // Rebuilding the whole heap takes O(n) time, which beats bubbling each
+        // new element up once a lot of them have been added.
+        if added > old_len {
+            self.heapify();
         }
         else {
-            (i - 1) / 2
+            for i in old_len..self.vector.len() {
+                self.bubble_up(i);
+            }
         }
     }
+}
 
-    // Returns true if i dominates j. False otherwise.
-    // Assumes i and j are valid indices into self.vector.
-    fn dominates(&self, i: usize, j: usize) -> bool {
-        (self.is_max_heap && self.vector[i] > self.vector[j])
-            || (!self.is_max_heap && self.vector[i] < self.vector[j])
+/// Iterates over the elements in no particular order.
+impl<T, C, const D: usize> IntoIterator for Heap<T, C, D> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vector.into_iter()
+    }
+}
+
+/// Iterates over the elements in no particular order.
+impl<'a, T, C, const D: usize> IntoIterator for &'a Heap<T, C, D> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.vector.iter()
+    }
+}
+
+/// A mutable reference to the first element of a [`Heap`]. When dropped, the
+/// element is moved to wherever its new value belongs. Created by
+/// Heap::peek_mut().
+pub struct PeekMut<'a, T, C, const D: usize = 2>
+    where C: Comparator<T>
+{
+    heap: &'a mut Heap<T, C, D>
+}
+
+impl<'a, T, C, const D: usize> PeekMut<'a, T, C, D>
+    where C: Comparator<T>
+{
+    /// Removes the peeked element from the heap and returns it.
+    pub fn pop(this: PeekMut<'a, T, C, D>) -> T {
+        // Sift the element back into place first, in case it was changed,
+        // so that extract() takes the right one. Dropping the guard then
+        // sifts the new first element, which is already in place.
+        this.heap.bubble_down(0);
+        // peek_mut() only creates a guard for a non-empty heap.
+        this.heap.extract().unwrap()
+    }
+}
+
+impl<'a, T, C, const D: usize> std::ops::Deref for PeekMut<'a, T, C, D>
+    where C: Comparator<T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.heap.vector[0]
+    }
+}
+
+impl<'a, T, C, const D: usize> std::ops::DerefMut for PeekMut<'a, T, C, D>
+    where C: Comparator<T>
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.heap.vector[0]
+    }
+}
+
+impl<'a, T, C, const D: usize> Drop for PeekMut<'a, T, C, D>
+    where C: Comparator<T>
+{
+    fn drop(&mut self) {
+        self.heap.bubble_down(0);
+    }
+}
+
+/// Iterator that extracts the elements of a [`Heap`] in order.
+/// Created by Heap::drain_sorted().
+pub struct DrainSorted<'a, T, C, const D: usize = 2>
+    where C: Comparator<T>
+{
+    heap: &'a mut Heap<T, C, D>
+}
+
+impl<'a, T, C, const D: usize> Iterator for DrainSorted<'a, T, C, D>
+    where C: Comparator<T>
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.heap.extract()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl<'a, T, C, const D: usize> Drop for DrainSorted<'a, T, C, D>
+    where C: Comparator<T>
+{
+    fn drop(&mut self) {
+        // Drop whatever the caller didn't consume, without sorting it.
+        self.heap.vector.clear();
+    }
+}
+
+#[cfg(test)]
+mod stable_heap_tests {
+    use super::*;
+
+    // A (priority, id) pair, with ids given out in insertion order.
+    type Job = (u32, usize);
+
+    fn priority(job: &Job) -> u32 {
+        job.0
+    }
+
+    #[test]
+    fn equal_elements_in_insertion_order() {
+        let mut heap = StableHeap::by_key(priority);
+        for (id, priority) in [2, 1, 2, 1, 3, 1, 2].into_iter().enumerate() {
+            heap.insert((priority, id));
+        }
+        assert_eq!(heap.peek(), Some(&(1, 1)));
+        let ids: Vec<usize> = heap.into_sorted_vec().into_iter().map(|job| job.1).collect();
+        assert_eq!(ids, vec![1, 3, 5, 0, 2, 6, 4]);
+    }
+
+    #[test]
+    fn interleaved_inserts_and_extracts() {
+        let mut heap = StableHeap::by_key(priority);
+        // The expected order: the lowest priority first, then the lowest id.
+        let mut model: Vec<Job> = Vec::new();
+        let mut next_id = 0;
+        for round in 0..200u32 {
+            for _ in 0..3 {
+                let job = ((round * 7 + next_id as u32) % 4, next_id);
+                heap.insert(job);
+                model.push(job);
+                next_id += 1;
+            }
+            for _ in 0..2 {
+                let first = *model.iter().min().unwrap();
+                model.retain(|job| *job != first);
+                assert_eq!(heap.extract(), Some(first));
+            }
+        }
+        assert_eq!(heap.len(), model.len());
+        model.sort();
+        assert_eq!(heap.into_sorted_vec(), model);
+    }
+
+    #[test]
+    fn meld_keeps_insertion_order() {
+        let length = |s: &&str| s.len();
+        let mut first = StableHeap::by_key(length);
+        let mut second = StableHeap::by_key(length);
+        first.extend(["bb", "a", "cc"]);
+        second.extend(["dd", "e", "ff", "g"]);
+        second.extract();
+        first.meld(second);
+        assert_eq!(first.into_sorted_vec(), vec!["a", "g", "bb", "cc", "dd", "ff"]);
+    }
+}
+
+// An element of a StableHeap, tagged with the order it was inserted in.
+struct Sequenced<T> {
+    sequence: u64,
+    item: T
+}
+
+// Orders elements by another comparator, then by insertion order.
+struct InsertionOrder<C>(C);
+
+impl<T, C> Comparator<Sequenced<T>> for InsertionOrder<C>
+    where C: Comparator<T>
+{
+    fn compare(&self, a: &Sequenced<T>, b: &Sequenced<T>) -> Ordering {
+        self.0.compare(&a.item, &b.item).then(a.sequence.cmp(&b.sequence))
+    }
+
+    fn same_order(&self, other: &InsertionOrder<C>) -> bool {
+        self.0.same_order(&other.0)
+    }
+}
+
+/// A heap that extracts elements the [`Comparator`] considers equal in the
+/// order they were inserted, which a plain [`Heap`] doesn't guarantee. This
+/// makes it suitable for fair scheduling.
+// Each element is tagged with an increasing sequence number, which breaks
+// ties in the comparator.
+pub struct StableHeap<T, C = NaturalOrder> {
+    next_sequence: u64,
+    heap: Heap<Sequenced<T>, InsertionOrder<C>>
+}
+
+impl<T> StableHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty stable heap. If the is_max_heap argument is true,
+    /// this will be a max-heap. If the argument is false, it will be a
+    /// min-heap.
+    pub fn new(is_max_heap: bool) -> StableHeap<T> {
+        StableHeap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, F> StableHeap<T, ByKey<F>> {
+    /// Creates a new empty stable heap that extracts the element with the
+    /// smallest key first, where key(element) computes an element's key.
+    pub fn by_key<K>(key: F) -> StableHeap<T, ByKey<F>>
+        where F: Fn(&T) -> K, K: PartialOrd
+    {
+        StableHeap::with_comparator(ByKey { key })
+    }
+}
+
+impl<T, C> StableHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty stable heap that extracts elements in the order
+    /// given by the comparator (see [`Comparator`]).
+    pub fn with_comparator(comparator: C) -> StableHeap<T, C> {
+        StableHeap {
+            next_sequence: 0,
+            heap: Heap::with_comparator(InsertionOrder(comparator))
+        }
+    }
+
+    /// The number of elements in the heap.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns true if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Returns the element that will be extracted next, without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.peek().map(|entry| &entry.item)
+    }
+
+    /// Inserts an item into the heap. It comes out after every element
+    /// already in the heap that the comparator considers equal to it.
+    pub fn insert(&mut self, item: T) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.heap.insert(Sequenced { sequence, item });
+    }
+
+    /// Removes the element that comes first and returns it. Of several equal
+    /// elements, the one inserted first comes out first. Returns None if the
+    /// heap is empty.
+    pub fn extract(&mut self) -> Option<T> {
+        self.heap.extract().map(|entry| entry.item)
+    }
+
+    /// Returns the elements of the heap in the order they would be
+    /// extracted in.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.heap.into_sorted_vec().into_iter().map(|entry| entry.item).collect()
+    }
+}
+
+// The comparator is left out, since closures can't be printed.
+impl<T, C> fmt::Debug for StableHeap<T, C>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<&T> = self.heap.vector.iter().map(|entry| &entry.item).collect();
+        f.debug_struct("StableHeap").field("vector", &items).finish()
+    }
+}
+
+/// The elements of other count as inserted after every element of self, in
+/// the order they were inserted into other. Melding takes O(n + m log m)
+/// time, since other's elements have to be put back in insertion order.
+impl<T, C> PriorityQueue<T> for StableHeap<T, C>
+    where C: Comparator<T>
+{
+    fn insert(&mut self, item: T) {
+        StableHeap::insert(self, item);
+    }
+
+    fn extract(&mut self) -> Option<T> {
+        StableHeap::extract(self)
+    }
+
+    fn peek(&self) -> Option<&T> {
+        StableHeap::peek(self)
+    }
+
+    fn len(&self) -> usize {
+        StableHeap::len(self)
+    }
+
+    fn meld(&mut self, other: StableHeap<T, C>) {
+        let mut entries = other.heap.vector;
+        entries.sort_unstable_by_key(|entry| entry.sequence);
+        self.extend(entries.into_iter().map(|entry| entry.item));
+    }
+}
+
+impl<T, C> Extend<T> for StableHeap<T, C>
+    where C: Comparator<T>
+{
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        let next_sequence = &mut self.next_sequence;
+        self.heap.extend(iter.into_iter().map(|item| {
+            let sequence = *next_sequence;
+            *next_sequence += 1;
+            Sequenced { sequence, item }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod indexed_heap_tests {
+    use super::*;
+
+    // Extract everything left in the heap, in order.
+    fn drain<T, C, const D: usize>(heap: &mut IndexedHeap<T, C, D>) -> Vec<T>
+        where C: Comparator<T>
+    {
+        let mut extracted = Vec::new();
+        while let Some(item) = heap.extract() {
+            extracted.push(item);
+        }
+        extracted
+    }
+
+    #[test]
+    fn insert_and_extract() {
+        let mut heap = IndexedHeap::new(false);
+        assert!(heap.extract().is_none());
+        for i in [5, -2, 9, 0, 3, 3] {
+            heap.insert(i);
+        }
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.peek(), Some(&-2));
+        assert_eq!(drain(&mut heap), vec![-2, 0, 3, 3, 5, 9]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn change_keys() {
+        let mut heap = IndexedHeap::new(false);
+        let handles: Vec<HeapHandle<i32>> = (0..10).map(|i| heap.insert(i * 10)).collect();
+
+        assert_eq!(heap.decrease_key(handles[7], -1), Some(70));
+        assert_eq!(heap.increase_key(handles[0], 55), Some(0));
+        assert_eq!(heap.change_priority(handles[9], 25), Some(90));
+        assert_eq!(heap.change_priority(handles[1], 100), Some(10));
+        assert_eq!(heap.get(handles[7]), Some(&-1));
+        assert_eq!(drain(&mut heap), vec![-1, 20, 25, 30, 40, 50, 55, 60, 80, 100]);
+
+        // Extracted elements can't be changed.
+        assert!(!heap.contains(handles[3]));
+        assert_eq!(heap.decrease_key(handles[3], 0), None);
+    }
+
+    #[test]
+    fn max_heap() {
+        let mut heap = IndexedHeap::new(true);
+        let handles: Vec<HeapHandle<i32>> = (0..5).map(|i| heap.insert(i)).collect();
+        // In a max-heap, moving an element towards the front means making it
+        // larger.
+        heap.decrease_key(handles[1], 10);
+        heap.increase_key(handles[4], -1);
+        assert_eq!(drain(&mut heap), vec![10, 3, 2, 0, -1]);
+    }
+
+    #[test]
+    fn arity() {
+        let mut heap = IndexedHeap::new(false);
+        let handles: Vec<HeapHandle<i32>> = (0..100).map(|i| heap.insert(i)).collect();
+        let mut heap = heap.into_arity::<4>();
+        for (i, handle) in handles.iter().enumerate().skip(50) {
+            heap.decrease_key(*handle, -(i as i32));
+        }
+        let extracted = drain(&mut heap);
+        assert_eq!(extracted[0], -99);
+        assert_eq!(extracted[49], -50);
+        assert_eq!(extracted[50..], (0..50).collect::<Vec<_>>()[..]);
+
+        let mut heap = IndexedHeap::with_arity::<3>(NaturalOrder::new(true));
+        let handles: Vec<HeapHandle<i32>> = (0..30).map(|i| heap.insert(i)).collect();
+        heap.decrease_key(handles[3], 100);
+        assert_eq!(drain(&mut heap)[..3], [100, 29, 28]);
+    }
+
+    #[test]
+    #[should_panic(expected = "decrease_key() was given an element that comes out later")]
+    fn decrease_key_wrong_way() {
+        let mut heap = IndexedHeap::new(false);
+        let handle = heap.insert(1);
+        heap.decrease_key(handle, 2);
+    }
+
+    #[test]
+    fn remove() {
+        let mut heap = IndexedHeap::new(false);
+        let handles: Vec<HeapHandle<i32>> = (0..20).map(|i| heap.insert((i * 7) % 20)).collect();
+        for handle in handles.iter().step_by(3) {
+            assert!(heap.contains(*handle));
+            assert!(heap.remove(*handle).is_some());
+            assert!(!heap.contains(*handle));
+            assert!(heap.remove(*handle).is_none());
+        }
+
+        let mut expected: Vec<i32> = (0..20).filter(|i| i % 3 != 0).map(|i| (i * 7) % 20).collect();
+        expected.sort();
+        assert_eq!(drain(&mut heap), expected);
+    }
+
+    // Find the shortest distances from node 0, the way the heap is meant to
+    // be used.
+    #[test]
+    fn dijkstra() {
+        let edges = [(0, 1, 4), (0, 2, 1), (2, 1, 2), (1, 3, 1), (2, 3, 5), (3, 4, 3)];
+        let mut distance = [u32::MAX; 5];
+        distance[0] = 0;
+
+        let mut heap = IndexedHeap::by_key(|&(_, d): &(usize, u32)| d);
+        let handles: Vec<HeapHandle<(usize, u32)>> =
+            (0..5).map(|node| heap.insert((node, distance[node]))).collect();
+        while let Some((node, d)) = heap.extract() {
+            for &(_, to, weight) in edges.iter().filter(|e| e.0 == node) {
+                if d + weight < distance[to] {
+                    distance[to] = d + weight;
+                    heap.decrease_key(handles[to], (to, distance[to]));
+                }
+            }
+        }
+        assert_eq!(distance, [0, 3, 1, 4, 7]);
+    }
+}
+
+/// An element of an [`IndexedHeap`] along with its position in the heap.
+/// Only reachable through the heap.
+pub struct HeapEntry<T> {
+    item: T,
+    // The element's index in the heap's vector of handles.
+    position: usize
+}
+
+/// Refers to an element of an [`IndexedHeap`]. Stays valid until that element
+/// is extracted or removed.
+pub type HeapHandle<T> = Key<HeapEntry<T>>;
+
+/// A heap that returns a handle from insert(), which can later be used to
+/// change the element's priority or remove it in O(log n). Useful for
+/// algorithms such as Dijkstra's and Prim's that revise the priority of queued
+/// elements. Ordered by a [`Comparator`] and with D children per element,
+/// like [`Heap`]. A 4-ary or 8-ary heap often suits decrease_key()-heavy work,
+/// since moving an element up gets cheaper as the heap gets shallower.
+// The elements live in an arena and never move. The heap itself is a vector
+// of handles, and every element records where its handle currently is so it
+// can be found again.
+pub struct IndexedHeap<T, C = NaturalOrder, const D: usize = 2> {
+    comparator: C,
+    entries: Arena<HeapEntry<T>>,
+    vector: Vec<HeapHandle<T>>
+}
+
+impl<T> IndexedHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty heap. If the is_max_heap argument is true, this will
+    /// be a max-heap. If the argument is false, it will be a min-heap.
+    pub fn new(is_max_heap: bool) -> IndexedHeap<T> {
+        IndexedHeap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, F> IndexedHeap<T, ByKey<F>> {
+    /// Creates a new empty heap that extracts the element with the smallest
+    /// key first, where key(element) computes an element's key.
+    pub fn by_key<K>(key: F) -> IndexedHeap<T, ByKey<F>>
+        where F: Fn(&T) -> K, K: PartialOrd
+    {
+        IndexedHeap::with_comparator(ByKey { key })
+    }
+}
+
+impl<T, C> IndexedHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap that extracts elements in the order given by
+    /// the comparator (see [`Comparator`]).
+    pub fn with_comparator(comparator: C) -> IndexedHeap<T, C> {
+        IndexedHeap {
+            comparator,
+            entries: Arena::new(),
+            vector: Vec::new()
+        }
+    }
+
+    /// Creates a new empty heap like with_comparator(), but with E children
+    /// per element. E must be at least 2.
+    pub fn with_arity<const E: usize>(comparator: C) -> IndexedHeap<T, C, E> {
+        IndexedHeap {
+            comparator,
+            entries: Arena::new(),
+            vector: Vec::new()
+        }
+    }
+}
+
+impl<T, C, const D: usize> IndexedHeap<T, C, D>
+    where C: Comparator<T>
+{
+    /// Rebuilds the heap with E children per element, in O(n) time. Handles
+    /// to the heap's elements stay valid. E must be at least 2.
+    pub fn into_arity<const E: usize>(self) -> IndexedHeap<T, C, E> {
+        let mut heap = IndexedHeap {
+            comparator: self.comparator,
+            entries: self.entries,
+            vector: self.vector
+        };
+        if heap.vector.len() > 1 {
+            for i in (0..=(heap.vector.len() - 2) / E).rev() {
+                heap.bubble_down(i);
+            }
+        }
+        heap
+    }
+
+    /// The number of elements in the heap.
+    pub fn len(&self) -> usize {
+        self.vector.len()
+    }
+
+    /// Returns true if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.vector.is_empty()
+    }
+
+    /// Inserts an item into the heap and returns a handle to it.
+    pub fn insert(&mut self, item: T) -> HeapHandle<T> {
+        let position = self.vector.len();
+        let handle = self.entries.insert(HeapEntry { item, position });
+        self.vector.push(handle);
+        self.bubble_up(position);
+        handle
+    }
+
+    /// Returns the element that will be extracted next, without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.vector.first().map(|handle| &self.entries[*handle].item)
+    }
+
+    /// Removes the element that comes first in the heap's order and returns
+    /// it. Returns None if the heap is empty.
+    pub fn extract(&mut self) -> Option<T> {
+        let first = *self.vector.first()?;
+        self.remove(first)
+    }
+
+    /// Returns true if the handle's element is still in the heap.
+    pub fn contains(&self, handle: HeapHandle<T>) -> bool {
+        self.entries.contains(handle)
+    }
+
+    /// Returns a reference to the handle's element, or None if it is no
+    /// longer in the heap.
+    pub fn get(&self, handle: HeapHandle<T>) -> Option<&T> {
+        self.entries.get(handle).map(|entry| &entry.item)
+    }
+
+    /// Removes the handle's element from the heap and returns it, or None if
+    /// it is no longer in the heap.
+    pub fn remove(&mut self, handle: HeapHandle<T>) -> Option<T> {
+        let position = self.entries.get(handle)?.position;
+        let last = self.vector.len() - 1;
+        self.swap(position, last);
+        self.vector.pop();
+        let entry = self.entries.remove(handle).
+            expect("The handle was just found in the heap. This is a bug in IndexedHeap.");
+
+        // The element that took the removed one's place may belong either
+        // above or below it.
+        if position < self.vector.len() {
+            self.bubble_up(position);
+            self.bubble_down(position);
+        }
+        Some(entry.item)
+    }
+
+    /// Replaces the handle's element with one that comes out no later than
+    /// it (e.g. a smaller value in a min-heap, or a larger one in a max-heap)
+    /// and returns the old element. Returns None (and drops item) if the
+    /// handle's element is no longer in the heap.
+    /// # Panics
+    /// Panics if the new element comes out later than the old one. Use
+    /// change_priority() if the direction isn't known.
+    pub fn decrease_key(&mut self, handle: HeapHandle<T>, item: T) -> Option<T> {
+        let entry = self.entries.get(handle)?;
+        if self.comparator.compare(&item, &entry.item) == Ordering::Greater {
+            panic!("decrease_key() was given an element that comes out later than the one it replaces.");
+        }
+        self.change_priority(handle, item)
+    }
+
+    /// Replaces the handle's element with one that comes out no earlier than
+    /// it (e.g. a larger value in a min-heap, or a smaller one in a max-heap)
+    /// and returns the old element. Returns None (and drops item) if the
+    /// handle's element is no longer in the heap.
+    /// # Panics
+    /// Panics if the new element comes out earlier than the old one. Use
+    /// change_priority() if the direction isn't known.
+    pub fn increase_key(&mut self, handle: HeapHandle<T>, item: T) -> Option<T> {
+        let entry = self.entries.get(handle)?;
+        if self.comparator.compare(&item, &entry.item) == Ordering::Less {
+            panic!("increase_key() was given an element that comes out earlier than the one it replaces.");
+        }
+        self.change_priority(handle, item)
+    }
+
+    /// Replaces the handle's element with any other element, moving it to
+    /// its new place in the heap, and returns the old element. Returns None
+    /// (and drops item) if the handle's element is no longer in the heap.
+    pub fn change_priority(&mut self, handle: HeapHandle<T>, item: T) -> Option<T> {
+        let entry = self.entries.get_mut(handle)?;
+        let old = std::mem::replace(&mut entry.item, item);
+        let position = entry.position;
+        // At most one of these moves the element.
+        self.bubble_up(position);
+        self.bubble_down(position);
+        Some(old)
+    }
+
+    // Moves the element at index i up the heap until it is dominated by its
+    // parent.
+    fn bubble_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let p = parent::<D>(i);
+            if !self.dominates(i, p) {
+                break;
+            }
+            self.swap(i, p);
+            i = p;
+        }
+    }
+
+    // Starting from index i, swap the current element with its most dominant
+    // child until it dominates all of its children.
+    fn bubble_down(&mut self, mut i: usize) {
+        loop {
+            let first = first_child::<D>(i);
+            let end = first.saturating_add(D).min(self.vector.len());
+            let mut i_dom = i;
+            for child in first..end {
+                if self.dominates(child, i_dom) {
+                    i_dom = child;
+                }
+            }
+
+            if i_dom == i {
+                return;
+            }
+            self.swap(i, i_dom);
+            i = i_dom;
+        }
+    }
+
+    // Swaps the elements at indices i and j, keeping their recorded positions
+    // up to date.
+    fn swap(&mut self, i: usize, j: usize) {
+        self.vector.swap(i, j);
+        self.entries[self.vector[i]].position = i;
+        self.entries[self.vector[j]].position = j;
+    }
+
+    // Returns true if the element at index i comes out before the one at j.
+    fn dominates(&self, i: usize, j: usize) -> bool {
+        let a = &self.entries[self.vector[i]].item;
+        let b = &self.entries[self.vector[j]].item;
+        self.comparator.compare(a, b) == Ordering::Less
+    }
+}
+
+#[cfg(test)]
+mod min_max_heap_tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn empty() {
+        let mut heap: MinMaxHeap<i32> = MinMaxHeap::new();
+        assert!(heap.peek_min().is_none());
+        assert!(heap.peek_max().is_none());
+        assert!(heap.pop_min().is_none());
+        assert!(heap.pop_max().is_none());
+
+        heap.insert(1);
+        assert_eq!(heap.peek_min(), Some(&1));
+        assert_eq!(heap.peek_max(), Some(&1));
+        assert_eq!(heap.pop_max(), Some(1));
+        assert!(heap.is_empty());
+    }
+
+    // Pop from both ends in an irregular pattern and compare against a
+    // sorted list.
+    #[test]
+    fn pop_from_both_ends() {
+        let mut heap = MinMaxHeap::new();
+        let mut values: Vec<i32> = (0..500).map(|i| (i * 7919) % 313 - 150).collect();
+        for v in values.iter() {
+            heap.insert(*v);
+        }
+        values.sort();
+        let mut expected = VecDeque::from(values);
+
+        let mut i = 0;
+        while !expected.is_empty() {
+            assert_eq!(heap.len(), expected.len());
+            assert_eq!(heap.peek_min(), expected.front());
+            assert_eq!(heap.peek_max(), expected.back());
+            if (i * i) % 3 == 0 {
+                assert_eq!(heap.pop_min(), expected.pop_front());
+            }
+            else {
+                assert_eq!(heap.pop_max(), expected.pop_back());
+            }
+            // Refill every now and then.
+            if i % 7 == 0 {
+                heap.insert(i);
+                let position = expected.partition_point(|v| *v < i);
+                expected.insert(position, i);
+            }
+            i += 1;
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn with_comparator() {
+        // "Min" is whatever the comparator puts first: here the longest
+        // string.
+        let mut heap = MinMaxHeap::with_comparator(|a: &&str, b: &&str| b.len().cmp(&a.len()));
+        for s in ["aaa", "a", "aaaaa", "aa", "aaaa"] {
+            heap.insert(s);
+        }
+        assert_eq!(heap.pop_min(), Some("aaaaa"));
+        assert_eq!(heap.pop_max(), Some("a"));
+        assert_eq!(heap.pop_max(), Some("aa"));
+        assert_eq!(heap.pop_min(), Some("aaaa"));
+        assert_eq!(heap.pop_min(), Some("aaa"));
+    }
+}
+
+/// A double-ended priority queue: both the smallest and the largest element
+/// can be looked at in O(1) time and removed in O(log n) time. Ordered by a
+/// [`Comparator`], where the "min" is the element the comparator puts first
+/// and the "max" the one it puts last.
+// Stored in a vector with the same layout as a binary Heap, but the levels
+// of the tree alternate: every element on an even level (starting with the
+// root) comes before all of its descendants, and every element on an odd
+// level comes after all of its descendants. So the min is the root and the
+// max is one of the root's children.
+pub struct MinMaxHeap<T, C = NaturalOrder> {
+    comparator: C,
+    vector: Vec<T>
+}
+
+impl<T> Default for MinMaxHeap<T>
+    where T: PartialOrd
+{
+    fn default() -> MinMaxHeap<T> {
+        MinMaxHeap::new()
+    }
+}
+
+impl<T> MinMaxHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty heap, ordered from smallest to largest.
+    pub fn new() -> MinMaxHeap<T> {
+        MinMaxHeap::with_comparator(NaturalOrder::new(false))
+    }
+}
+
+impl<T, C> MinMaxHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap ordered by the comparator (see
+    /// [`Comparator`]).
+    pub fn with_comparator(comparator: C) -> MinMaxHeap<T, C> {
+        MinMaxHeap {
+            comparator,
+            vector: Vec::new()
+        }
+    }
+
+    /// The number of elements in the heap.
+    pub fn len(&self) -> usize {
+        self.vector.len()
+    }
+
+    /// Returns true if the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.vector.is_empty()
+    }
+
+    /// Inserts an item into the heap.
+    pub fn insert(&mut self, item: T) {
+        self.vector.push(item);
+        let i = self.vector.len() - 1;
+        if i == 0 {
+            return;
+        }
+
+        // The new element may belong on the other kind of level than the one
+        // it was added on. If so, it trades places with its parent first.
+        let p = (i - 1) / 2;
+        let is_min = Self::is_min_level(i);
+        if self.before(p, i, is_min) {
+            self.vector.swap(i, p);
+            self.bubble_up(p, !is_min);
+        }
+        else {
+            self.bubble_up(i, is_min);
+        }
+    }
+
+    /// Returns the smallest element (the one the comparator puts first).
+    pub fn peek_min(&self) -> Option<&T> {
+        self.vector.first()
+    }
+
+    /// Returns the largest element (the one the comparator puts last).
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|i| &self.vector[i])
+    }
+
+    /// Removes and returns the smallest element.
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.remove_at(0)
+    }
+
+    /// Removes and returns the largest element.
+    pub fn pop_max(&mut self) -> Option<T> {
+        let i = self.max_index()?;
+        self.remove_at(i)
+    }
+
+    // Returns the index of the largest element, or None if the heap is empty.
+    fn max_index(&self) -> Option<usize> {
+        match self.vector.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ => if self.before(1, 2, false) { Some(1) } else { Some(2) }
+        }
+    }
+
+    // Remove the element at index i (the min or the max) and fill the gap
+    // with the last element.
+    fn remove_at(&mut self, i: usize) -> Option<T> {
+        if i >= self.vector.len() {
+            return None;
+        }
+        let item = self.vector.swap_remove(i);
+        if i < self.vector.len() {
+            self.trickle_down(i, Self::is_min_level(i));
+        }
+        Some(item)
+    }
+
+    // Returns true if the element at index i is on a min level, i.e. an even
+    // level of the tree.
+    fn is_min_level(i: usize) -> bool {
+        (i + 1).ilog2().is_multiple_of(2)
+    }
+
+    // On a min level, returns true if the element at i comes before the one at
+    // j. On a max level, returns true if it comes after.
+    fn before(&self, i: usize, j: usize, is_min: bool) -> bool {
+        let ordering = self.comparator.compare(&self.vector[i], &self.vector[j]);
+        if is_min {
+            ordering == Ordering::Less
+        }
+        else {
+            ordering == Ordering::Greater
+        }
+    }
+
+    // Moves the element at index i up through the levels of its own kind,
+    // swapping with its grandparent, until it is in order.
+    fn bubble_up(&mut self, mut i: usize, is_min: bool) {
+        while i > 2 {
+            let grandparent = (((i - 1) / 2) - 1) / 2;
+            if !self.before(i, grandparent, is_min) {
+                break;
+            }
+            self.vector.swap(i, grandparent);
+            i = grandparent;
+        }
+    }
+
+    // Moves the element at index i down the heap until it is in order with
+    // its children and grandchildren.
+    fn trickle_down(&mut self, mut i: usize, is_min: bool) {
+        loop {
+            // Find the most extreme of the (up to six) children and
+            // grandchildren.
+            let mut m = i;
+            let first_child = (2 * i) + 1;
+            let first_grandchild = (2 * first_child) + 1;
+            let descendants = (first_child..first_child + 2).chain(first_grandchild..first_grandchild + 4);
+            for d in descendants.take_while(|d| *d < self.vector.len()) {
+                if self.before(d, m, is_min) {
+                    m = d;
+                }
+            }
+            if m == i {
+                return;
+            }
+
+            self.vector.swap(i, m);
+            if m < first_grandchild {
+                // Swapping with a child can't break the order below it.
+                return;
+            }
+            // The element that moved down to m may now be out of order with
+            // m's parent, which is on the other kind of level.
+            let p = (m - 1) / 2;
+            if self.before(p, m, is_min) {
+                self.vector.swap(m, p);
+            }
+            i = m;
+        }
+    }
+}
+
+// The comparator is left out, since closures can't be printed.
+impl<T, C> fmt::Debug for MinMaxHeap<T, C>
+    where T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MinMaxHeap").field("vector", &self.vector).finish()
+    }
+}
+
+#[cfg(test)]
+mod top_k_tests {
+    use super::*;
+
+    // The values 0..1000 in a scrambled order.
+    fn scrambled() -> impl Iterator<Item = i32> {
+        (0..1000).map(|i| (i * 7919) % 1000)
+    }
+
+    #[test]
+    fn largest_and_smallest() {
+        let mut largest = TopK::largest(5);
+        let mut smallest = TopK::smallest(3);
+        assert!(largest.threshold().is_none());
+        for i in scrambled() {
+            largest.insert(i);
+            smallest.insert(i);
+        }
+        assert_eq!(largest.len(), 5);
+        assert_eq!(largest.threshold(), Some(&995));
+        assert_eq!(largest.into_sorted_vec(), vec![999, 998, 997, 996, 995]);
+        assert_eq!(smallest.threshold(), Some(&2));
+        assert_eq!(smallest.into_sorted_vec(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn fewer_than_k() {
+        let mut top = TopK::largest(10);
+        top.extend([3, 1, 2]);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top.into_sorted_vec(), vec![3, 2, 1]);
+
+        let mut none = TopK::largest(0);
+        none.insert(1);
+        assert!(none.is_empty());
+        assert!(none.into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn helpers() {
+        assert_eq!(top_k(scrambled(), 4), vec![999, 998, 997, 996]);
+        let words = ["pear", "fig", "banana", "kiwi", "apple"];
+        let longest = top_k_by(words, 2, |a: &&str, b: &&str| b.len().cmp(&a.len()));
+        assert_eq!(longest, vec!["banana", "apple"]);
+    }
+
+    #[test]
+    fn running_median() {
+        let mut median = RunningMedian::new();
+        assert!(median.median().is_none());
+
+        let mut seen = Vec::new();
+        for i in scrambled().take(201) {
+            median.insert(i);
+            seen.push(i);
+            seen.sort();
+            assert_eq!(median.len(), seen.len());
+            assert_eq!(median.median(), Some(&seen[(seen.len() - 1) / 2]));
+            assert_eq!(median.upper_median(), Some(&seen[seen.len() / 2]));
+        }
+    }
+}
+
+// Reverses the order of another comparator.
+struct Reversed<C>(C);
+
+impl<T, C> Comparator<T> for Reversed<C>
+    where C: Comparator<T>
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(b, a)
+    }
+
+    fn same_order(&self, other: &Reversed<C>) -> bool {
+        self.0.same_order(&other.0)
+    }
+}
+
+/// Keeps the k best elements seen so far, where the best are the ones the
+/// [`Comparator`] puts first (e.g. the k largest with largest()). Elements
+/// are added one at a time, in O(log k) time each, so a stream of any length
+/// can be scanned in O(k) memory.
+// Backed by a heap of the opposite order, so that the worst of the kept
+// elements is always on top, ready to be replaced by something better.
+pub struct TopK<T, C = NaturalOrder> {
+    k: usize,
+    heap: Heap<T, Reversed<C>>
+}
+
+impl<T> TopK<T>
+    where T: PartialOrd
+{
+    /// Creates a TopK that keeps the k largest elements.
+    pub fn largest(k: usize) -> TopK<T> {
+        TopK::with_comparator(k, NaturalOrder::new(true))
+    }
+
+    /// Creates a TopK that keeps the k smallest elements.
+    pub fn smallest(k: usize) -> TopK<T> {
+        TopK::with_comparator(k, NaturalOrder::new(false))
+    }
+}
+
+impl<T, C> TopK<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a TopK that keeps the k elements the comparator puts first.
+    pub fn with_comparator(k: usize, comparator: C) -> TopK<T, C> {
+        TopK {
+            k,
+            heap: Heap::with_comparator(Reversed(comparator))
+        }
+    }
+
+    /// The number of elements kept so far. At most k.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns true if nothing has been kept.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The worst of the kept elements. Once k elements are kept, only
+    /// elements that come before this one are kept from then on.
+    pub fn threshold(&self) -> Option<&T> {
+        self.heap.peek()
+    }
+
+    /// Offers an element, which is kept if fewer than k elements have been
+    /// kept or if it comes before the worst of them, which it then replaces.
+    pub fn insert(&mut self, item: T) {
+        if self.heap.len() < self.k {
+            self.heap.insert(item);
+        }
+        else if let Some(worst) = self.heap.peek() {
+            // The heap's comparator is reversed, so "Greater" means item
+            // comes before worst in the original order.
+            if self.heap.comparator.compare(&item, worst) == Ordering::Greater {
+                // Overwrite the worst element and let the guard sift the new
+                // one into place. peek() has just found an element.
+                *self.heap.peek_mut().unwrap() = item;
+            }
+        }
+    }
+
+    /// Returns the kept elements, best first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        let mut sorted = self.heap.into_sorted_vec();
+        sorted.reverse();
+        sorted
+    }
+}
+
+impl<T, C> Extend<T> for TopK<T, C>
+    where C: Comparator<T>
+{
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+/// Returns the k largest elements of iter, largest first.
+pub fn top_k<I, T>(iter: I, k: usize) -> Vec<T>
+    where I: IntoIterator<Item = T>, T: PartialOrd
+{
+    let mut top = TopK::largest(k);
+    top.extend(iter);
+    top.into_sorted_vec()
+}
+
+/// Returns the k elements of iter that the comparator puts first, in that
+/// order.
+pub fn top_k_by<I, T, C>(iter: I, k: usize, comparator: C) -> Vec<T>
+    where I: IntoIterator<Item = T>, C: Comparator<T>
+{
+    let mut top = TopK::with_comparator(k, comparator);
+    top.extend(iter);
+    top.into_sorted_vec()
+}
+
+/// Tracks the median of a stream of elements. Each insert takes O(log n)
+/// time and the median can be read in O(1) time.
+// The smaller half of the elements is kept in a max-heap and the larger half
+// in a min-heap, so the middle elements are at the tops of the heaps. The
+// lower half holds the extra element when the count is odd.
+pub struct RunningMedian<T> {
+    lower: Heap<T>,
+    upper: Heap<T>
+}
+
+impl<T> Default for RunningMedian<T>
+    where T: PartialOrd
+{
+    fn default() -> RunningMedian<T> {
+        RunningMedian::new()
+    }
+}
+
+impl<T> RunningMedian<T>
+    where T: PartialOrd
+{
+    /// Creates an empty RunningMedian.
+    pub fn new() -> RunningMedian<T> {
+        RunningMedian {
+            lower: Heap::new(true),
+            upper: Heap::new(false)
+        }
+    }
+
+    /// The number of elements seen.
+    pub fn len(&self) -> usize {
+        self.lower.len() + self.upper.len()
+    }
+
+    /// Returns true if no elements have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.lower.is_empty()
+    }
+
+    /// Adds an element to the stream.
+    pub fn insert(&mut self, item: T) {
+        match self.lower.peek() {
+            Some(lower_max) if item > *lower_max => self.upper.insert(item),
+            _ => self.lower.insert(item)
+        }
+
+        // Move an element across if either half got too big.
+        if self.lower.len() > self.upper.len() + 1 {
+            // lower holds at least two elements.
+            self.upper.insert(self.lower.extract().unwrap());
+        }
+        else if self.upper.len() > self.lower.len() {
+            self.lower.insert(self.upper.extract().unwrap());
+        }
+    }
+
+    /// The median of the elements seen so far. With an even number of
+    /// elements, this is the lower of the two middle elements (see
+    /// upper_median()).
+    pub fn median(&self) -> Option<&T> {
+        self.lower.peek()
     }
 
-    // Compares the elements at indices i and j and returns the index of the
-    // dominant element, based on whether this is a max or min heap.
-    // Assumes that i and j are valid indices into self.vector.
-    fn get_dominant(&self, i: usize, j: usize) -> usize {
-        if self.dominates(i, j) {
-            i
+    /// The upper of the two middle elements if the number of elements seen
+    /// is even, and the median if it is odd.
+    pub fn upper_median(&self) -> Option<&T> {
+        if self.upper.len() == self.lower.len() {
+            self.upper.peek()
         }
         else {
-            j
+            self.lower.peek()
         }
     }
 }
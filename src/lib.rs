@@ -1,6 +1,35 @@
 pub mod arena;
 pub mod collections;
+pub mod encoding;
+// The lints allowed on the modules below only fire on code that predates the
+// clippy gate, which is left as it was written.
+#[allow(
+    clippy::assign_op_pattern,
+    clippy::items_after_test_module,
+    clippy::needless_borrow,
+    clippy::neg_multiply,
+    clippy::new_without_default,
+    clippy::no_effect,
+    clippy::redundant_field_names
+)]
 pub mod hashset;
 pub mod heap;
+pub mod mergeable_heaps;
+#[allow(
+    clippy::drain_collect,
+    clippy::len_zero,
+    clippy::manual_range_contains,
+    clippy::ptr_arg,
+    clippy::question_mark,
+    clippy::useless_vec
+)]
 pub mod sort_and_search;
+#[allow(
+    clippy::manual_map,
+    clippy::needless_late_init,
+    clippy::new_without_default,
+    clippy::question_mark,
+    clippy::unnecessary_sort_by,
+    clippy::unnecessary_unwrap
+)]
 pub mod trees;
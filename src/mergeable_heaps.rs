@@ -0,0 +1,1152 @@
+use crate::arena::{Arena, Key};
+use crate::heap::{Comparator, NaturalOrder, PriorityQueue};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops;
+use std::sync::atomic::{self, AtomicU64};
+
+// Heaps that can be melded (merged) with another heap of the same kind faster
+// than by moving the elements one at a time:
+//  - LeftistHeap: O(log n) meld, insert and extract.
+//  - BinomialHeap: O(log n) meld, insert, extract and decrease_key.
+//  - PairingHeap: O(1) insert, amortized O(log n) extract and decrease_key.
+// The last two keep their nodes in arenas so that they can be reached through
+// handles. Melding takes over the other heap's arenas instead of moving its
+// nodes, so it stays cheap and handles keep working.
+
+#[cfg(test)]
+mod test_helpers {
+    use crate::heap::PriorityQueue;
+    use std::cmp::Ordering;
+
+    // Extract everything from a queue, in order.
+    pub fn extract_all<T, Q>(queue: &mut Q) -> Vec<T>
+        where Q: PriorityQueue<T>
+    {
+        let mut extracted = Vec::new();
+        while let Some(item) = queue.extract() {
+            extracted.push(item);
+        }
+        extracted
+    }
+
+    // Fill two queues with scrambled, overlapping values, meld them and make
+    // sure everything comes out in ascending order.
+    pub fn check_meld<Q>(mut a: Q, mut b: Q)
+        where Q: PriorityQueue<i32>
+    {
+        for i in 0..200 {
+            a.insert((i * 37) % 200);
+            b.insert((i * 53) % 150 - 50);
+        }
+        a.meld(b);
+        assert_eq!(a.len(), 400);
+
+        let mut expected: Vec<i32> = (0..200).chain((0..200).map(|i| (i * 53) % 150 - 50)).collect();
+        expected.sort();
+        assert_eq!(extract_all(&mut a), expected);
+        assert!(a.is_empty());
+        assert!(a.peek().is_none());
+    }
+
+    // Orders ascending or descending depending on what it captures, so heaps
+    // ordered both ways share one comparator type.
+    pub fn directed(descending: bool) -> impl Fn(&i32, &i32) -> Ordering {
+        move |a: &i32, b: &i32| if descending { b.cmp(a) } else { a.cmp(b) }
+    }
+
+    // Meld a max-heap into a min-heap, which has to keep extracting the
+    // smallest element first.
+    pub fn check_meld_opposite_order<Q>(mut min: Q, mut max: Q)
+        where Q: PriorityQueue<i32>
+    {
+        for i in 0..100 {
+            min.insert((i * 37) % 100);
+            max.insert((i * 53) % 100 + 50);
+        }
+        min.meld(max);
+        let mut expected: Vec<i32> = (0..100).chain(50..150).collect();
+        expected.sort();
+        assert_eq!(extract_all(&mut min), expected);
+    }
+}
+
+/// Refers to an element of a [`PairingHeap`] or a [`BinomialHeap`]. Stays
+/// valid until that element is extracted or removed, including after its
+/// heap is melded into another one.
+pub struct Handle<N> {
+    // The id of the segment the element's node was inserted into.
+    segment: u64,
+    key: Key<N>
+}
+
+// These are implemented by hand, like Key's, because deriving them would
+// require N to implement them too.
+impl<N> Clone for Handle<N> {
+    fn clone(&self) -> Handle<N> {
+        *self
+    }
+}
+
+impl<N> Copy for Handle<N> {}
+
+impl<N> PartialEq for Handle<N> {
+    fn eq(&self, other: &Handle<N>) -> bool {
+        self.segment == other.segment && self.key == other.key
+    }
+}
+
+impl<N> Eq for Handle<N> {}
+
+impl<N> Hash for Handle<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.segment.hash(state);
+        self.key.hash(state);
+    }
+}
+
+impl<N> fmt::Debug for Handle<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle").
+            field("segment", &self.segment).
+            field("key", &self.key).
+            finish()
+    }
+}
+
+// The nodes of a heap that can be melded without moving them. New nodes go
+// into the heap's own arena, and melding takes over the other heap's arenas
+// (its segments) whole. Every segment has an id that is unique across all
+// heaps, and a handle records the segment its node lives in, so handles to
+// either heap keep working after a meld.
+struct Segments<N> {
+    id: u64,
+    own: Arena<N>,
+    // Segments taken over from melded heaps, by id. Each one is dropped once
+    // it is empty.
+    melded: HashMap<u64, Arena<N>>,
+    len: usize
+}
+
+impl<N> Segments<N> {
+    fn new() -> Segments<N> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Segments {
+            id: NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            own: Arena::new(),
+            melded: HashMap::new(),
+            len: 0
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn insert(&mut self, node: N) -> Handle<N> {
+        self.len += 1;
+        Handle { segment: self.id, key: self.own.insert(node) }
+    }
+
+    fn get(&self, handle: Handle<N>) -> Option<&N> {
+        let arena = if handle.segment == self.id {
+            &self.own
+        }
+        else {
+            self.melded.get(&handle.segment)?
+        };
+        arena.get(handle.key)
+    }
+
+    fn get_mut(&mut self, handle: Handle<N>) -> Option<&mut N> {
+        let arena = if handle.segment == self.id {
+            &mut self.own
+        }
+        else {
+            self.melded.get_mut(&handle.segment)?
+        };
+        arena.get_mut(handle.key)
+    }
+
+    fn remove(&mut self, handle: Handle<N>) -> Option<N> {
+        let node = if handle.segment == self.id {
+            self.own.try_remove(handle.key).ok()?
+        }
+        else {
+            let arena = self.melded.get_mut(&handle.segment)?;
+            let node = arena.try_remove(handle.key).ok()?;
+            if arena.is_empty() {
+                self.melded.remove(&handle.segment);
+            }
+            node
+        };
+        self.len -= 1;
+        Some(node)
+    }
+
+    // Handles to every node, in no particular order.
+    fn handles(&self) -> Vec<Handle<N>> {
+        let own = self.own.indices().map(|key| Handle { segment: self.id, key });
+        let melded = self.melded.iter().flat_map(|(&segment, arena)| {
+            arena.indices().map(move |key| Handle { segment, key })
+        });
+        own.chain(melded).collect()
+    }
+
+    // Take over other's segments. The side that has taken over fewer
+    // segments before is the one whose segments get moved, so this takes
+    // O(1) time unless both heaps were themselves built by melding.
+    fn absorb(&mut self, mut other: Segments<N>) {
+        if other.melded.len() > self.melded.len() {
+            std::mem::swap(&mut self.melded, &mut other.melded);
+        }
+        self.melded.extend(other.melded);
+        if !other.own.is_empty() {
+            self.melded.insert(other.id, other.own);
+        }
+        self.len += other.len;
+    }
+}
+
+// Indexing is for the links between nodes, which always lead to live nodes.
+impl<N> ops::Index<Handle<N>> for Segments<N> {
+    type Output = N;
+
+    fn index(&self, handle: Handle<N>) -> &N {
+        self.get(handle).expect("A link leads to a missing node. This is a bug in the heap.")
+    }
+}
+
+impl<N> ops::IndexMut<Handle<N>> for Segments<N> {
+    fn index_mut(&mut self, handle: Handle<N>) -> &mut N {
+        self.get_mut(handle).expect("A link leads to a missing node. This is a bug in the heap.")
+    }
+}
+
+#[cfg(test)]
+mod leftist_heap_tests {
+    use super::*;
+    use super::test_helpers::{check_meld, check_meld_opposite_order, directed, extract_all};
+
+    #[test]
+    fn insert_and_extract() {
+        let mut heap = LeftistHeap::new(false);
+        assert!(heap.extract().is_none());
+        for i in [4, 1, 3, 1, 5, 9, 2, 6] {
+            heap.insert(i);
+        }
+        assert_eq!(heap.peek(), Some(&1));
+        assert_eq!(extract_all(&mut heap), vec![1, 1, 2, 3, 4, 5, 6, 9]);
+
+        let mut heap = LeftistHeap::new(true);
+        heap.insert(String::from("b"));
+        heap.insert(String::from("c"));
+        heap.insert(String::from("a"));
+        assert_eq!(extract_all(&mut heap), vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn meld() {
+        check_meld(LeftistHeap::new(false), LeftistHeap::new(false));
+        check_meld_opposite_order(LeftistHeap::new(false), LeftistHeap::new(true));
+        check_meld_opposite_order(LeftistHeap::with_comparator(directed(false)),
+            LeftistHeap::with_comparator(directed(true)));
+    }
+
+    // Dropping a large heap shouldn't overflow the stack, however lopsided
+    // it is.
+    #[test]
+    fn drop_large() {
+        let mut heap = LeftistHeap::new(true);
+        for i in 0..1_000_000 {
+            heap.insert(i);
+        }
+    }
+}
+
+// A node of a LeftistHeap. Its rank is the length of the path down its right
+// children to the first missing child. A heap is leftist when every node's
+// left child has a rank at least as large as its right child's, which keeps
+// the right path of every subtree short.
+struct LeftistNode<T> {
+    item: T,
+    rank: usize,
+    left: Option<Box<LeftistNode<T>>>,
+    right: Option<Box<LeftistNode<T>>>
+}
+
+/// A leftist heap. Ordered by a [`Comparator`], like
+/// [`Heap`](crate::heap::Heap), and melds two heaps in O(log n) time.
+/// It has no handles or decrease_key(): its nodes don't link to their
+/// parents, and moving an element up would mean cutting its subtree off and
+/// fixing the ranks above it. Use a [`BinomialHeap`] or a [`PairingHeap`]
+/// when elements need to change.
+pub struct LeftistHeap<T, C = NaturalOrder> {
+    comparator: C,
+    root: Option<Box<LeftistNode<T>>>,
+    len: usize
+}
+
+impl<T> LeftistHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty heap. If the is_max_heap argument is true, this will
+    /// be a max-heap. If the argument is false, it will be a min-heap.
+    pub fn new(is_max_heap: bool) -> LeftistHeap<T> {
+        LeftistHeap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, C> LeftistHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap that extracts elements in the order given by
+    /// the comparator.
+    pub fn with_comparator(comparator: C) -> LeftistHeap<T, C> {
+        LeftistHeap {
+            comparator,
+            root: None,
+            len: 0
+        }
+    }
+
+    // Merge two leftist trees along their right paths. Both paths have
+    // O(log n) nodes, which bounds the recursion.
+    fn merge(&self, a: Option<Box<LeftistNode<T>>>, b: Option<Box<LeftistNode<T>>>)
+        -> Option<Box<LeftistNode<T>>>
+    {
+        let (mut a, mut b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, None) => return a,
+            (None, b) => return b
+        };
+
+        // Keep the node that comes first on top.
+        if self.comparator.compare(&b.item, &a.item) == Ordering::Less {
+            std::mem::swap(&mut a, &mut b);
+        }
+        let right = a.right.take();
+        a.right = self.merge(right, Some(b));
+
+        // Restore the leftist property by swapping the children if needed.
+        let rank = |node: &Option<Box<LeftistNode<T>>>| node.as_ref().map_or(0, |n| n.rank);
+        if rank(&a.left) < rank(&a.right) {
+            std::mem::swap(&mut a.left, &mut a.right);
+        }
+        a.rank = rank(&a.right) + 1;
+        Some(a)
+    }
+}
+
+impl<T, C> PriorityQueue<T> for LeftistHeap<T, C>
+    where C: Comparator<T>
+{
+    fn insert(&mut self, item: T) {
+        let node = Box::new(LeftistNode { item, rank: 1, left: None, right: None });
+        let root = self.root.take();
+        self.root = self.merge(root, Some(node));
+        self.len += 1;
+    }
+
+    fn extract(&mut self) -> Option<T> {
+        let root = self.root.take()?;
+        let root = *root;
+        self.root = self.merge(root.left, root.right);
+        self.len -= 1;
+        Some(root.item)
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|root| &root.item)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Takes O(log n + log m) time if both comparators are known to put
+    /// elements in the same order (see Comparator::same_order()). Otherwise
+    /// other's elements are inserted one at a time.
+    fn meld(&mut self, mut other: LeftistHeap<T, C>) {
+        if !self.comparator.same_order(&other.comparator) {
+            while let Some(item) = other.extract() {
+                self.insert(item);
+            }
+            return;
+        }
+        let root = self.root.take();
+        self.root = self.merge(root, other.root.take());
+        self.len += other.len;
+    }
+}
+
+// The left paths of a leftist heap can be as long as the heap is big, so the
+// nodes are dropped with an explicit stack rather than recursively.
+impl<T, C> Drop for LeftistHeap<T, C> {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<LeftistNode<T>>> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod binomial_heap_tests {
+    use super::*;
+    use super::test_helpers::{check_meld, check_meld_opposite_order, directed, extract_all};
+
+    #[test]
+    fn insert_and_extract() {
+        let mut heap = BinomialHeap::new(false);
+        assert!(heap.extract().is_none());
+        for i in [4, 1, 3, 1, 5, 9, 2, 6, 5, 3, 5] {
+            heap.insert(i);
+        }
+        assert_eq!(heap.len(), 11);
+        assert_eq!(heap.peek(), Some(&1));
+        assert_eq!(extract_all(&mut heap), vec![1, 1, 2, 3, 3, 4, 5, 5, 5, 6, 9]);
+
+        let mut heap = BinomialHeap::with_comparator(|a: &(i32, char), b: &(i32, char)| {
+            b.0.cmp(&a.0)
+        });
+        heap.insert((1, 'a'));
+        heap.insert((3, 'b'));
+        heap.insert((2, 'c'));
+        assert_eq!(extract_all(&mut heap), vec![(3, 'b'), (2, 'c'), (1, 'a')]);
+    }
+
+    #[test]
+    fn meld() {
+        check_meld(BinomialHeap::new(false), BinomialHeap::new(false));
+        check_meld_opposite_order(BinomialHeap::new(false), BinomialHeap::new(true));
+        check_meld_opposite_order(BinomialHeap::with_comparator(directed(false)),
+            BinomialHeap::with_comparator(directed(true)));
+
+        // Meld heaps of every pair of sizes, to exercise every pattern of
+        // carries.
+        for n in 0..12 {
+            for m in 0..12 {
+                let mut a = BinomialHeap::new(true);
+                let mut b = BinomialHeap::new(true);
+                for i in 0..n {
+                    a.insert(i);
+                }
+                for i in 0..m {
+                    b.insert(i);
+                }
+                a.meld(b);
+                let mut expected: Vec<i32> = (0..n).chain(0..m).collect();
+                expected.sort_by(|x, y| y.cmp(x));
+                assert_eq!(extract_all(&mut a), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn decrease_key_and_remove() {
+        let mut heap = BinomialHeap::new(false);
+        let handles: Vec<BinomialHandle<i32>> = (0..100).map(|i| heap.insert(i * 10)).collect();
+        assert_eq!(heap.extract(), Some(0));
+        assert!(!heap.contains(handles[0]));
+
+        // The largest element is a leaf, so it moves all the way up to a root.
+        assert_eq!(heap.decrease_key(handles[99], -1), Some(990));
+        assert_eq!(heap.peek(), Some(&-1));
+        assert_eq!(heap.decrease_key(handles[50], 5), Some(500));
+        assert_eq!(heap.get(handles[50]), Some(&5));
+        // The elements they moved past keep their handles.
+        for i in (1..99).filter(|i| *i != 50) {
+            assert_eq!(heap.get(handles[i]), Some(&(i as i32 * 10)));
+        }
+        assert_eq!(heap.remove(handles[99]), Some(-1));
+        assert_eq!(heap.remove(handles[20]), Some(200));
+        assert_eq!(heap.remove(handles[20]), None);
+        assert_eq!(heap.decrease_key(handles[20], 0), None);
+
+        let mut expected: Vec<i32> = (1..99).filter(|i| *i != 20 && *i != 50).map(|i| i * 10).collect();
+        expected.insert(0, 5);
+        assert_eq!(heap.len(), expected.len());
+        assert_eq!(extract_all(&mut heap), expected);
+    }
+
+    // Removing every element by handle, in an order unrelated to the heap's,
+    // exercises removal from every depth of every tree.
+    #[test]
+    fn remove_all() {
+        let mut heap = BinomialHeap::new(true);
+        let handles: Vec<BinomialHandle<i32>> = (0..64).map(|i| heap.insert((i * 37) % 64)).collect();
+        for i in 0..64 {
+            let j = (i * 5) % 64;
+            assert_eq!(heap.remove(handles[j]), Some(((j * 37) % 64) as i32));
+            assert_eq!(heap.len(), 63 - i);
+        }
+        assert!(heap.is_empty());
+        assert!(heap.trees.is_empty());
+    }
+
+    #[test]
+    fn handles_after_meld() {
+        let mut a = BinomialHeap::new(false);
+        let mut b = BinomialHeap::new(false);
+        let mut c = BinomialHeap::new(true);
+        let a_handles: Vec<BinomialHandle<i32>> = (0..10).map(|i| a.insert(i)).collect();
+        let b_handles: Vec<BinomialHandle<i32>> = (10..20).map(|i| b.insert(i)).collect();
+        let c_handles: Vec<BinomialHandle<i32>> = (20..30).map(|i| c.insert(i)).collect();
+        a.meld(b);
+        a.meld(c);
+        assert_eq!(a.len(), 30);
+        assert_eq!(a.decrease_key(b_handles[9], -1), Some(19));
+        assert_eq!(a.decrease_key(c_handles[0], -2), Some(20));
+        assert_eq!(a.remove(a_handles[3]), Some(3));
+        let mut expected: Vec<i32> = (0..30).filter(|i| ![3, 19, 20].contains(i)).collect();
+        expected.insert(0, -1);
+        expected.insert(0, -2);
+        assert_eq!(extract_all(&mut a), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "decrease_key() was given an element that comes out later")]
+    fn decrease_key_wrong_way() {
+        let mut heap = BinomialHeap::new(true);
+        let handle = heap.insert(1);
+        heap.decrease_key(handle, 0);
+    }
+}
+
+/// An element of a [`BinomialHeap`]. Only reachable through the heap.
+pub struct BinomialEntry<T> {
+    item: T,
+    // The tree node that holds the element. Only None while insert() is
+    // making the node.
+    node: Option<BinomialLink<T>>
+}
+
+/// Refers to an element of a [`BinomialHeap`]. See [`Handle`].
+pub type BinomialHandle<T> = Handle<BinomialEntry<T>>;
+
+// A node of a BinomialHeap. A node of order k has k children, of orders
+// k - 1 down to 0, so its tree holds 2^k elements. decrease_key() moves
+// elements up their trees, so a node refers to its element through a handle
+// instead of holding it, and moving an element only swaps handles.
+struct BinomialNode<T> {
+    entry: BinomialHandle<T>,
+    parent: Option<BinomialLink<T>>,
+    // Children in increasing order: children[i] has order i.
+    children: Vec<BinomialLink<T>>
+}
+
+type BinomialLink<T> = Handle<BinomialNode<T>>;
+
+/// A binomial heap. Ordered by a [`Comparator`], like
+/// [`Heap`](crate::heap::Heap), and melds two heaps in O(log n) time.
+/// Insert returns a handle that can be used to move the element towards the
+/// front with decrease_key() or to remove it, both in O(log n) time.
+// The heap is a forest with at most one tree of each order, like the bits of
+// the binary representation of its length. Melding two heaps works like
+// adding two binary numbers, where linking two trees of order k into one of
+// order k + 1 is the carry.
+pub struct BinomialHeap<T, C = NaturalOrder> {
+    comparator: C,
+    entries: Segments<BinomialEntry<T>>,
+    nodes: Segments<BinomialNode<T>>,
+    // trees[k] holds the root of the tree of order k, if there is one.
+    trees: Vec<Option<BinomialLink<T>>>
+}
+
+impl<T> BinomialHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty heap. If the is_max_heap argument is true, this will
+    /// be a max-heap. If the argument is false, it will be a min-heap.
+    pub fn new(is_max_heap: bool) -> BinomialHeap<T> {
+        BinomialHeap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, C> BinomialHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap that extracts elements in the order given by
+    /// the comparator.
+    pub fn with_comparator(comparator: C) -> BinomialHeap<T, C> {
+        BinomialHeap {
+            comparator,
+            entries: Segments::new(),
+            nodes: Segments::new(),
+            trees: Vec::new()
+        }
+    }
+
+    /// Inserts an item into the heap and returns a handle to it.
+    pub fn insert(&mut self, item: T) -> BinomialHandle<T> {
+        let entry = self.entries.insert(BinomialEntry { item, node: None });
+        let node = self.nodes.insert(BinomialNode { entry, parent: None, children: Vec::new() });
+        self.entries[entry].node = Some(node);
+        self.merge_trees(vec![Some(node)]);
+        entry
+    }
+
+    /// Returns true if the handle's element is still in the heap.
+    pub fn contains(&self, handle: BinomialHandle<T>) -> bool {
+        self.entries.get(handle).is_some()
+    }
+
+    /// Returns a reference to the handle's element, or None if it is no
+    /// longer in the heap.
+    pub fn get(&self, handle: BinomialHandle<T>) -> Option<&T> {
+        self.entries.get(handle).map(|entry| &entry.item)
+    }
+
+    /// Replaces the handle's element with one that comes out no later than
+    /// it (e.g. a smaller value in a min-heap) and returns the old element.
+    /// Returns None (and drops item) if the handle's element is no longer in
+    /// the heap.
+    /// # Panics
+    /// Panics if the new element comes out later than the old one.
+    pub fn decrease_key(&mut self, handle: BinomialHandle<T>, item: T) -> Option<T> {
+        let entry = self.entries.get_mut(handle)?;
+        if self.comparator.compare(&item, &entry.item) == Ordering::Greater {
+            panic!("decrease_key() was given an element that comes out later than the one it replaces.");
+        }
+        let old = std::mem::replace(&mut entry.item, item);
+        let node = self.node_of(handle);
+        self.sift_up(node, false);
+        Some(old)
+    }
+
+    /// Removes the handle's element from the heap and returns it, or None if
+    /// it is no longer in the heap.
+    pub fn remove(&mut self, handle: BinomialHandle<T>) -> Option<T> {
+        if !self.contains(handle) {
+            return None;
+        }
+        // Move the element to the root of its tree, as if it came first, and
+        // take it out from there.
+        let node = self.node_of(handle);
+        let root = self.sift_up(node, true);
+        Some(self.remove_root(self.nodes[root].children.len()))
+    }
+
+    // The node holding the element of a handle that is in the heap.
+    fn node_of(&self, handle: BinomialHandle<T>) -> BinomialLink<T> {
+        self.entries[handle].node.expect("Every element has a node once inserted. This is a bug in BinomialHeap.")
+    }
+
+    fn item(&self, node: BinomialLink<T>) -> &T {
+        &self.entries[self.nodes[node].entry].item
+    }
+
+    // Move a node's element up its tree, swapping it with its parent's, until
+    // it no longer comes before its parent's element, or all the way up to
+    // the root if to_root is true. Returns the node the element ends up in.
+    fn sift_up(&mut self, mut node: BinomialLink<T>, to_root: bool) -> BinomialLink<T> {
+        while let Some(parent) = self.nodes[node].parent {
+            if !to_root && self.comparator.compare(self.item(node), self.item(parent)) != Ordering::Less {
+                break;
+            }
+            let (moving_up, moving_down) = (self.nodes[node].entry, self.nodes[parent].entry);
+            self.nodes[parent].entry = moving_up;
+            self.nodes[node].entry = moving_down;
+            self.entries[moving_up].node = Some(parent);
+            self.entries[moving_down].node = Some(node);
+            node = parent;
+        }
+        node
+    }
+
+    // Remove the root of the tree of the given order and return its element.
+    fn remove_root(&mut self, order: usize) -> T {
+        let root = self.trees[order].take().
+            expect("There is no tree of that order. This is a bug in BinomialHeap.");
+        while let Some(None) = self.trees.last() {
+            self.trees.pop();
+        }
+        // The root always exists, and so does its entry.
+        let root = self.nodes.remove(root).unwrap();
+        let item = self.entries.remove(root.entry).unwrap().item;
+
+        // The root's children are a binomial heap of their own.
+        for &child in &root.children {
+            self.nodes[child].parent = None;
+        }
+        self.merge_trees(root.children.into_iter().map(Some).collect());
+        item
+    }
+
+    // Link two trees of the same order into one of the next order, keeping
+    // the root that comes first on top.
+    fn link(&mut self, mut a: BinomialLink<T>, mut b: BinomialLink<T>) -> BinomialLink<T> {
+        if self.comparator.compare(self.item(b), self.item(a)) == Ordering::Less {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.nodes[b].parent = Some(a);
+        self.nodes[a].children.push(b);
+        a
+    }
+
+    // Add trees (where trees[k] has order k) into the heap's forest.
+    fn merge_trees(&mut self, trees: Vec<Option<BinomialLink<T>>>) {
+        let mut carry = None;
+        let mut others = trees.into_iter();
+        let mut order = 0;
+        loop {
+            let other = others.next();
+            if other.is_none() && carry.is_none() && order >= self.trees.len() {
+                break;
+            }
+            if order == self.trees.len() {
+                self.trees.push(None);
+            }
+
+            // Add up to three trees of this order: keep one here and carry a
+            // linked pair on to the next order.
+            let mut present: Vec<BinomialLink<T>> =
+                [self.trees[order].take(), other.flatten(), carry.take()].
+                into_iter().
+                flatten().
+                collect();
+            if present.len() >= 2 {
+                let (b, a) = (present.pop().unwrap(), present.pop().unwrap());
+                carry = Some(self.link(a, b));
+            }
+            self.trees[order] = present.pop();
+            order += 1;
+        }
+    }
+
+    // Returns the order of the tree whose root comes first.
+    fn first_tree(&self) -> Option<usize> {
+        let mut first: Option<usize> = None;
+        for (order, tree) in self.trees.iter().enumerate() {
+            if let Some(tree) = *tree {
+                let comes_first = match first {
+                    Some(f) => self.comparator.compare(
+                        self.item(tree), self.item(self.trees[f].unwrap())) == Ordering::Less,
+                    None => true
+                };
+                if comes_first {
+                    first = Some(order);
+                }
+            }
+        }
+        first
+    }
+}
+
+impl<T, C> PriorityQueue<T> for BinomialHeap<T, C>
+    where C: Comparator<T>
+{
+    fn insert(&mut self, item: T) {
+        BinomialHeap::insert(self, item);
+    }
+
+    fn extract(&mut self) -> Option<T> {
+        let order = self.first_tree()?;
+        Some(self.remove_root(order))
+    }
+
+    /// Takes O(log n) time, since the roots of every tree are compared.
+    fn peek(&self) -> Option<&T> {
+        self.first_tree().map(|order| self.item(self.trees[order].unwrap()))
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Takes O(log n + log m) time if both comparators are known to put
+    /// elements in the same order (see Comparator::same_order()). Otherwise
+    /// other's elements are linked in one at a time. Either way, handles to
+    /// other's elements keep working as handles into this heap.
+    fn meld(&mut self, other: BinomialHeap<T, C>) {
+        self.entries.absorb(other.entries);
+        if self.comparator.same_order(&other.comparator) {
+            self.nodes.absorb(other.nodes);
+            self.merge_trees(other.trees);
+            return;
+        }
+
+        // other's trees are in the wrong order, so take its nodes apart.
+        let nodes = other.nodes.handles();
+        self.nodes.absorb(other.nodes);
+        for node in nodes {
+            self.nodes[node].parent = None;
+            self.nodes[node].children.clear();
+            self.merge_trees(vec![Some(node)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod pairing_heap_tests {
+    use super::*;
+    use super::test_helpers::{check_meld, check_meld_opposite_order, directed, extract_all};
+
+    #[test]
+    fn insert_and_extract() {
+        let mut heap = PairingHeap::new(false);
+        assert!(heap.extract().is_none());
+        for i in [4, 1, 3, 1, 5, 9, 2, 6] {
+            heap.insert(i);
+        }
+        assert_eq!(heap.peek(), Some(&1));
+        assert_eq!(extract_all(&mut heap), vec![1, 1, 2, 3, 4, 5, 6, 9]);
+    }
+
+    #[test]
+    fn meld() {
+        check_meld(PairingHeap::new(false), PairingHeap::new(false));
+        check_meld_opposite_order(PairingHeap::new(false), PairingHeap::new(true));
+        check_meld_opposite_order(PairingHeap::with_comparator(directed(false)),
+            PairingHeap::with_comparator(directed(true)));
+
+        // Handles to both heaps keep working in the melded heap.
+        let mut a = PairingHeap::new(false);
+        let mut b = PairingHeap::new(false);
+        let ten = a.insert(10);
+        let twenty = b.insert(20);
+        b.insert(30);
+        a.meld(b);
+        assert_eq!(a.decrease_key(twenty, 5), Some(20));
+        assert_eq!(a.get(ten), Some(&10));
+        assert_eq!(extract_all(&mut a), vec![5, 10, 30]);
+    }
+
+    // Handles survive chains of melds, and melding heaps that were themselves
+    // melded, however their elements are removed afterwards.
+    #[test]
+    fn handles_after_repeated_melds() {
+        let mut heaps: Vec<PairingHeap<i32>> = Vec::new();
+        let mut handles = Vec::new();
+        for i in 0..16 {
+            let mut heap = PairingHeap::new(false);
+            handles.push(heap.insert(i));
+            handles.push(heap.insert(i + 100));
+            heaps.push(heap);
+        }
+        // Meld them pairwise, like a tournament.
+        while heaps.len() > 1 {
+            let right = heaps.split_off(heaps.len() / 2);
+            for (left, right) in heaps.iter_mut().zip(right) {
+                left.meld(right);
+            }
+        }
+        let mut heap = heaps.pop().unwrap();
+        assert_eq!(heap.len(), 32);
+
+        for (i, handle) in handles.iter().enumerate() {
+            let expected = (i / 2) as i32 + if i % 2 == 0 { 0 } else { 100 };
+            assert_eq!(heap.get(*handle), Some(&expected));
+        }
+        assert_eq!(heap.remove(handles[7]), Some(103));
+        assert_eq!(heap.remove(handles[6]), Some(3));
+        assert!(!heap.contains(handles[6]));
+        assert_eq!(heap.decrease_key(handles[31], -1), Some(115));
+        assert_eq!(heap.extract(), Some(-1));
+        assert_eq!(heap.len(), 29);
+    }
+
+    // Melding a heap with a different order relinks its nodes one by one,
+    // which has to keep its handles working too.
+    #[test]
+    fn handles_after_meld_opposite_order() {
+        let mut min = PairingHeap::new(false);
+        let mut max = PairingHeap::new(true);
+        for i in 0..10 {
+            min.insert(i * 2);
+        }
+        let handles: Vec<PairingHandle<i32>> = (0..10).map(|i| max.insert(i * 2 + 1)).collect();
+        min.meld(max);
+        assert_eq!(min.decrease_key(handles[9], -1), Some(19));
+        assert_eq!(min.remove(handles[0]), Some(1));
+        let mut expected: Vec<i32> = (0..19).filter(|i| *i != 1).collect();
+        expected.insert(0, -1);
+        assert_eq!(extract_all(&mut min), expected);
+    }
+
+    #[test]
+    fn handle_from_other_heap() {
+        let mut heap = PairingHeap::new(false);
+        heap.insert(1);
+        let mut other = PairingHeap::new(false);
+        let handle = other.insert(2);
+        assert!(!heap.contains(handle));
+        assert_eq!(heap.get(handle), None);
+        assert_eq!(heap.remove(handle), None);
+        assert_eq!(heap.len(), 1);
+    }
+
+    #[test]
+    fn decrease_key_and_remove() {
+        let mut heap = PairingHeap::new(false);
+        let handles: Vec<PairingHandle<i32>> = (0..100).map(|i| heap.insert(i * 10)).collect();
+        // Extract once so the heap is no longer a flat list of the root's
+        // children.
+        assert_eq!(heap.extract(), Some(0));
+        assert!(!heap.contains(handles[0]));
+
+        assert_eq!(heap.decrease_key(handles[50], 5), Some(500));
+        assert_eq!(heap.decrease_key(handles[99], -1), Some(990));
+        assert_eq!(heap.get(handles[50]), Some(&5));
+        assert_eq!(heap.remove(handles[99]), Some(-1));
+        assert_eq!(heap.remove(handles[20]), Some(200));
+        assert_eq!(heap.remove(handles[20]), None);
+        assert_eq!(heap.decrease_key(handles[20], 0), None);
+
+        let mut expected: Vec<i32> = (1..99).filter(|i| *i != 20 && *i != 50).map(|i| i * 10).collect();
+        expected.insert(0, 5);
+        assert_eq!(heap.len(), expected.len());
+        assert_eq!(extract_all(&mut heap), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "decrease_key() was given an element that comes out later")]
+    fn decrease_key_wrong_way() {
+        let mut heap = PairingHeap::new(true);
+        let handle = heap.insert(1);
+        heap.decrease_key(handle, 0);
+    }
+}
+
+/// A node of a [`PairingHeap`] along with its links. Only reachable through
+/// the heap.
+pub struct PairingNode<T> {
+    item: T,
+    // The node's first child.
+    child: Option<PairingHandle<T>>,
+    // The node's next sibling.
+    next: Option<PairingHandle<T>>,
+    // The node's previous sibling, or its parent if it is the first child.
+    // None for the root.
+    prev: Option<PairingHandle<T>>
+}
+
+/// Refers to an element of a [`PairingHeap`]. See [`Handle`].
+pub type PairingHandle<T> = Handle<PairingNode<T>>;
+
+/// A pairing heap. Ordered by a [`Comparator`], like
+/// [`Heap`](crate::heap::Heap). Insert returns a handle that can be used to
+/// move the element towards the front with decrease_key() or to remove it.
+/// Inserting takes O(1) time, extracting and decrease_key() amortized
+/// O(log n).
+// Each node keeps its children in a linked list. Extracting the root pairs
+// up its children from left to right, then links the pairs from right to
+// left, which is what gives the heap its amortized bounds.
+pub struct PairingHeap<T, C = NaturalOrder> {
+    comparator: C,
+    nodes: Segments<PairingNode<T>>,
+    root: Option<PairingHandle<T>>
+}
+
+impl<T> PairingHeap<T>
+    where T: PartialOrd
+{
+    /// Creates a new empty heap. If the is_max_heap argument is true, this will
+    /// be a max-heap. If the argument is false, it will be a min-heap.
+    pub fn new(is_max_heap: bool) -> PairingHeap<T> {
+        PairingHeap::with_comparator(NaturalOrder::new(is_max_heap))
+    }
+}
+
+impl<T, C> PairingHeap<T, C>
+    where C: Comparator<T>
+{
+    /// Creates a new empty heap that extracts elements in the order given by
+    /// the comparator.
+    pub fn with_comparator(comparator: C) -> PairingHeap<T, C> {
+        PairingHeap {
+            comparator,
+            nodes: Segments::new(),
+            root: None
+        }
+    }
+
+    /// Inserts an item into the heap and returns a handle to it.
+    pub fn insert(&mut self, item: T) -> PairingHandle<T> {
+        let node = self.nodes.insert(PairingNode { item, child: None, next: None, prev: None });
+        self.root = self.merge(self.root, Some(node));
+        node
+    }
+
+    /// Returns true if the handle's element is still in the heap.
+    pub fn contains(&self, handle: PairingHandle<T>) -> bool {
+        self.nodes.get(handle).is_some()
+    }
+
+    /// Returns a reference to the handle's element, or None if it is no
+    /// longer in the heap.
+    pub fn get(&self, handle: PairingHandle<T>) -> Option<&T> {
+        self.nodes.get(handle).map(|node| &node.item)
+    }
+
+    /// Replaces the handle's element with one that comes out no later than
+    /// it (e.g. a smaller value in a min-heap) and returns the old element.
+    /// Returns None (and drops item) if the handle's element is no longer in
+    /// the heap.
+    /// # Panics
+    /// Panics if the new element comes out later than the old one.
+    pub fn decrease_key(&mut self, handle: PairingHandle<T>, item: T) -> Option<T> {
+        let node = self.nodes.get_mut(handle)?;
+        if self.comparator.compare(&item, &node.item) == Ordering::Greater {
+            panic!("decrease_key() was given an element that comes out later than the one it replaces.");
+        }
+        let old = std::mem::replace(&mut node.item, item);
+
+        // The node's subtree is still in order, but the node may now come
+        // before its parent. Cut the subtree off and link it to the root.
+        if self.root != Some(handle) {
+            self.cut(handle);
+            self.root = self.merge(self.root, Some(handle));
+        }
+        Some(old)
+    }
+
+    /// Removes the handle's element from the heap and returns it, or None if
+    /// it is no longer in the heap.
+    pub fn remove(&mut self, handle: PairingHandle<T>) -> Option<T> {
+        if !self.contains(handle) {
+            return None;
+        }
+        if self.root == Some(handle) {
+            return PriorityQueue::extract(self);
+        }
+
+        self.cut(handle);
+        // contains() has made sure the node exists.
+        let node = self.nodes.remove(handle).unwrap();
+        let children = self.combine_siblings(node.child);
+        self.root = self.merge(self.root, children);
+        Some(node.item)
+    }
+
+    /// Moves every element of other into this heap. Handles to other's
+    /// elements keep working as handles into this heap. Takes O(1) time
+    /// unless both heaps were themselves built by melding, in which case it
+    /// also takes time in the number of heaps melded into one of them.
+    /// This needs both comparators to be known to put elements in the same
+    /// order (see Comparator::same_order()). Otherwise other's elements are
+    /// linked in one at a time, which takes O(m) time.
+    pub fn meld(&mut self, other: PairingHeap<T, C>) {
+        if self.comparator.same_order(&other.comparator) {
+            self.nodes.absorb(other.nodes);
+            self.root = self.merge(self.root, other.root);
+            return;
+        }
+
+        // other's trees are in the wrong order, so take its nodes apart.
+        let handles = other.nodes.handles();
+        self.nodes.absorb(other.nodes);
+        for handle in handles {
+            let node = &mut self.nodes[handle];
+            node.child = None;
+            node.next = None;
+            node.prev = None;
+            self.root = self.merge(self.root, Some(handle));
+        }
+    }
+
+    // Make the root that comes later the first child of the other and return
+    // the combined tree's root. Either may be None.
+    fn merge(&mut self, a: Option<PairingHandle<T>>, b: Option<PairingHandle<T>>)
+        -> Option<PairingHandle<T>>
+    {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, None) => return a,
+            (None, b) => return b
+        };
+        let (first, second) =
+            if self.comparator.compare(&self.nodes[b].item, &self.nodes[a].item) == Ordering::Less {
+                (b, a)
+            }
+            else {
+                (a, b)
+            };
+
+        let old_child = self.nodes[first].child;
+        if let Some(old_child) = old_child {
+            self.nodes[old_child].prev = Some(second);
+        }
+        let second_node = &mut self.nodes[second];
+        second_node.prev = Some(first);
+        second_node.next = old_child;
+        self.nodes[first].child = Some(second);
+        Some(first)
+    }
+
+    // Detach a node that isn't the root, along with its subtree, from its
+    // parent and siblings.
+    fn cut(&mut self, handle: PairingHandle<T>) {
+        let node = &mut self.nodes[handle];
+        let prev = node.prev.take().
+            expect("Only the root has no previous node. This is a bug in PairingHeap.");
+        let next = node.next.take();
+
+        if self.nodes[prev].child == Some(handle) {
+            self.nodes[prev].child = next;
+        }
+        else {
+            self.nodes[prev].next = next;
+        }
+        if let Some(next) = next {
+            self.nodes[next].prev = Some(prev);
+        }
+    }
+
+    // Combine a list of sibling trees into a single tree and return its root:
+    // link the siblings in pairs from left to right, then link the pairs from
+    // right to left.
+    fn combine_siblings(&mut self, first: Option<PairingHandle<T>>) -> Option<PairingHandle<T>> {
+        let mut siblings = Vec::new();
+        let mut current = first;
+        while let Some(sibling) = current {
+            let node = &mut self.nodes[sibling];
+            current = node.next.take();
+            node.prev = None;
+            siblings.push(sibling);
+        }
+
+        let mut pairs = Vec::with_capacity(siblings.len() / 2 + 1);
+        for pair in siblings.chunks(2) {
+            pairs.push(self.merge(Some(pair[0]), pair.get(1).copied()));
+        }
+        let mut root = None;
+        while let Some(pair) = pairs.pop() {
+            root = self.merge(pair, root);
+        }
+        root
+    }
+}
+
+impl<T, C> PriorityQueue<T> for PairingHeap<T, C>
+    where C: Comparator<T>
+{
+    fn insert(&mut self, item: T) {
+        PairingHeap::insert(self, item);
+    }
+
+    fn extract(&mut self) -> Option<T> {
+        let root = self.root?;
+        // The root is always a live node.
+        let node = self.nodes.remove(root).unwrap();
+        self.root = self.combine_siblings(node.child);
+        Some(node.item)
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.root.map(|root| &self.nodes[root].item)
+    }
+
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// See PairingHeap::meld().
+    fn meld(&mut self, other: PairingHeap<T, C>) {
+        PairingHeap::meld(self, other);
+    }
+}
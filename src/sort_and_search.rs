@@ -1,5 +1,5 @@
-use std::cmp::Ord;
-use crate::heap::Heap;
+use std::cmp::{Ord, Ordering};
+use crate::heap::{self, Comparator, Heap, NaturalOrder};
 use crate::hashset::Hashset;
 
 #[cfg(test)]
@@ -268,6 +268,34 @@ mod heapsort_tests {
         assert_eq!(v.len(), len);
         test_helpers::assert_sorted(v.iter(), false);
     }
+
+    #[test]
+    fn sort_by() {
+        // Sort by absolute value.
+        let mut v = vec![-5, 4, -3, 1, 11, -10];
+        heapsort_by(&mut v, |a: &i32, b: &i32| a.abs().cmp(&b.abs()));
+        assert_eq!(v, vec![1, -3, 4, -5, -10, 11]);
+    }
+
+    #[test]
+    fn sort_slice() {
+        // Only the middle of the array is sorted.
+        let mut a = [9, 5, 4, 3, 1, 11, 10, 0];
+        heapsort(&mut a[1..7], false);
+        assert_eq!(a, [9, 1, 3, 4, 5, 10, 11, 0]);
+
+        heapsort(&mut a[..1], true);
+        assert_eq!(a[0], 9);
+    }
+
+    #[test]
+    fn sort_strings() {
+        let mut v = test_helpers::get_strings();
+        let mut expected = v.clone();
+        expected.sort();
+        heapsort(&mut v, false);
+        assert_eq!(v, expected);
+    }
 }
 
 #[cfg(test)]
@@ -320,6 +348,61 @@ mod mergesort_tests {
     }
 }
 
+#[cfg(test)]
+mod kway_merge_tests {
+    use super::*;
+
+    #[test]
+    fn merge_ascending_and_descending() {
+        let runs = vec![vec![1, 4, 7], vec![2, 5, 8, 9], vec![], vec![3, 6]];
+        let merged: Vec<i32> = kway_merge(runs, false).collect();
+        assert_eq!(merged, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        let runs = vec![vec![7, 4, 1], vec![9, 8, 5, 2], vec![6, 3]];
+        let merged: Vec<i32> = kway_merge(runs, true).collect();
+        assert_eq!(merged, vec![9, 8, 7, 6, 5, 4, 3, 2, 1]);
+
+        let none: Vec<Vec<i32>> = Vec::new();
+        assert_eq!(kway_merge(none, false).next(), None);
+    }
+
+    #[test]
+    fn merge_sorted_strings() {
+        // Split the strings into sorted runs of different lengths.
+        let mut strings = test_helpers::get_strings();
+        let total = strings.len();
+        let mut runs = Vec::new();
+        for len in 1..5 {
+            runs.push(mergesort(strings.drain(..len).collect(), false));
+        }
+        runs.push(mergesort(strings, false));
+
+        let merged: Vec<String> = kway_merge(runs, false).collect();
+        assert_eq!(merged.len(), total);
+        test_helpers::assert_sorted(merged.iter(), true);
+    }
+
+    #[test]
+    fn lazy_over_endless_runs() {
+        // Multiples of 2, 3 and 5, which never end.
+        let runs = [2, 3, 5].map(|step| (step..).step_by(step));
+        let merged: Vec<usize> = kway_merge(runs, false).take(8).collect();
+        assert_eq!(merged, vec![2, 3, 4, 5, 6, 6, 8, 9]);
+    }
+
+    #[test]
+    fn dedup() {
+        let runs = [2, 3, 5].map(|step| (step..).step_by(step));
+        let merged: Vec<usize> = kway_merge(runs, false).dedup().take(8).collect();
+        assert_eq!(merged, vec![2, 3, 4, 5, 6, 8, 9, 10]);
+
+        // Duplicates within one run are dropped too.
+        let runs = vec![vec![3, 3, 2, 1, 1], vec![3, 1], vec![1]];
+        let merged: Vec<i32> = kway_merge(runs, true).dedup().collect();
+        assert_eq!(merged, vec![3, 2, 1]);
+    }
+}
+
 #[cfg(test)]
 mod quicksort_tests {
     use super::*;
@@ -561,24 +644,28 @@ fn square_root_internal(x: f64, l: f64, h: f64) -> f64 {
 }
 */
 
-/// Sorts the given vector in ascending or descending order.
-pub fn heapsort<T>(v: &mut Vec<T>, sort_descending: bool)
+/// Sorts the given slice in place, in ascending or descending order.
+pub fn heapsort<T>(v: &mut [T], sort_descending: bool)
     where T: PartialOrd
 {
-    // Use a max-heap for descending sort and a min-heap for ascending.
-    let mut h = Heap::new(sort_descending);
-    
-    // Empty the vector into the heap.
-    for _ in 0..v.len() {
-        h.insert(v.pop().unwrap());
-    }
-    
-    // Empty the heap back into the vector.
-    // Like magic, the elements are now sorted!
-    let mut element = h.extract();
-    while element.is_some() {
-        v.push(element.unwrap());
-        element = h.extract();
+    heapsort_by(v, NaturalOrder::new(sort_descending));
+}
+
+/// Sorts the given slice in place so that the element the comparator puts
+/// first comes first (see [`Comparator`]). Uses O(1) extra memory.
+pub fn heapsort_by<T, C>(v: &mut [T], comparator: C)
+    where C: Comparator<T>
+{
+    // Build a heap whose root is the element that belongs at the end of the
+    // sorted slice, by reversing the comparator.
+    let reversed = |a: &T, b: &T| comparator.compare(b, a);
+    heap::heapify::<T, _, 2>(v, &reversed);
+
+    // Repeatedly move the root to the end of the heap and shrink the heap by
+    // one, so the sorted part grows from the back of the slice.
+    for end in (1..v.len()).rev() {
+        v.swap(0, end);
+        heap::sift_down::<T, _, 2>(&mut v[..end], 0, &reversed);
     }
 }
 
@@ -640,6 +727,116 @@ fn merge<T>(mut v1: Vec<T>, mut v2: Vec<T>, sort_descending: bool) -> Vec<T>
     merged
 }
 
+/// Merges runs that are each sorted in ascending or descending order into a
+/// single sorted run. The runs can be any iterators, and are read lazily:
+/// each call to next() takes O(log k) time for k runs. Of equal elements, the
+/// ones from earlier runs come out first. Call dedup() on the result to drop
+/// repeated elements.
+pub fn kway_merge<I>(iterators: I, sort_descending: bool)
+    -> KwayMerge<<I::Item as IntoIterator>::IntoIter>
+    where I: IntoIterator, I::Item: IntoIterator, <I::Item as IntoIterator>::Item: PartialOrd
+{
+    let mut runs = Vec::new();
+    // Use a max-heap for descending order and a min-heap for ascending.
+    let mut heads = Heap::with_comparator(RunOrder { order: NaturalOrder::new(sort_descending) });
+    for (run, iterator) in iterators.into_iter().enumerate() {
+        let mut iterator = iterator.into_iter();
+        if let Some(item) = iterator.next() {
+            heads.insert(RunHead { item, run });
+        }
+        runs.push(iterator);
+    }
+
+    KwayMerge {
+        runs,
+        heads,
+        dedup: false
+    }
+}
+
+// The next element of one of the runs given to kway_merge().
+struct RunHead<T> {
+    item: T,
+    run: usize
+}
+
+// Orders run heads by their elements, then by the order of their runs.
+struct RunOrder {
+    order: NaturalOrder
+}
+
+impl<T> Comparator<RunHead<T>> for RunOrder
+    where T: PartialOrd
+{
+    fn compare(&self, a: &RunHead<T>, b: &RunHead<T>) -> Ordering {
+        self.order.compare(&a.item, &b.item).then(a.run.cmp(&b.run))
+    }
+}
+
+/// Iterator over the merged elements of several sorted runs.
+/// Created by kway_merge().
+// Holds the first element of each run that hasn't been used up in a heap, so
+// the next element to come out is always on top.
+pub struct KwayMerge<I>
+    where I: Iterator
+{
+    runs: Vec<I>,
+    heads: Heap<RunHead<I::Item>, RunOrder>,
+    dedup: bool
+}
+
+impl<I> KwayMerge<I>
+    where I: Iterator, I::Item: PartialOrd
+{
+    /// Skip elements equal to the one that came out before them, so each
+    /// value comes out only once.
+    pub fn dedup(mut self) -> KwayMerge<I> {
+        self.dedup = true;
+        self
+    }
+
+    // Removes the top head and replaces it with the next element of its run.
+    fn pop_head(&mut self) -> Option<I::Item> {
+        let RunHead { item, run } = self.heads.extract()?;
+        if let Some(next) = self.runs[run].next() {
+            self.heads.insert(RunHead { item: next, run });
+        }
+        Some(item)
+    }
+}
+
+impl<I> Iterator for KwayMerge<I>
+    where I: Iterator, I::Item: PartialOrd
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.pop_head()?;
+        if self.dedup {
+            // The runs are sorted, so any copies of item are next in line.
+            while self.heads.peek().is_some_and(|head| head.item == item) {
+                self.pop_head();
+            }
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let mut lower = self.heads.len();
+        let mut upper = Some(self.heads.len());
+        for run in &self.runs {
+            let (run_lower, run_upper) = run.size_hint();
+            lower = lower.saturating_add(run_lower);
+            upper = upper.zip(run_upper).and_then(|(a, b)| a.checked_add(b));
+        }
+        if self.dedup {
+            // Every element left could be a copy of the first.
+            lower = lower.min(1);
+        }
+        (lower, upper)
+    }
+}
+
 /// Sorts the given vector in ascending or descending order.
 pub fn quicksort<T>(vector: &mut Vec<T>, sort_descending: bool)
     where T: PartialOrd
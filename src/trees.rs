@@ -1,4 +1,7 @@
-use crate::arena::Arena;
+use crate::arena::{Arena, ArenaCorruption, Key, SecondaryMap};
+use crate::encoding::{Decode, DecodeError, Encode};
+use std::error::Error;
+use std::fmt;
 
 #[cfg(test)]
 mod binary_search_tree_tests {
@@ -110,6 +113,157 @@ mod binary_search_tree_tests {
         }
     }
 
+    // Remove nodes with two children (including the root), then insert new keys
+    // that reuse the freed arena slots. Every remaining key must still be found.
+    #[test]
+    fn remove_and_reinsert() {
+        let mut tree = BinarySearchTree::new();
+        for key in [50, 25, 75, 10, 30, 60, 90, 27, 35] {
+            tree.insert(key, key);
+        }
+
+        // The root, a node whose successor is its right child, and a node whose
+        // successor is deeper in its right subtree.
+        assert_eq!(tree.remove(&50), Some(50));
+        assert_eq!(tree.remove(&75), Some(75));
+        assert_eq!(tree.remove(&25), Some(25));
+
+        for key in [1, 2, 3] {
+            tree.insert(key, key);
+        }
+
+        let expected = vec![1, 2, 3, 10, 27, 30, 35, 60, 90];
+        assert_eq!(tree.as_vector(), expected.iter().collect::<Vec<_>>());
+        for key in [50, 75, 25] {
+            assert!(tree.search(&key).is_none());
+        }
+
+        // Removing every key leaves an empty tree that can be used again.
+        for key in expected {
+            assert_eq!(tree.remove(&key), Some(key));
+        }
+        assert!(tree.as_vector().is_empty());
+        tree.insert(5, 5);
+        assert_eq!(*tree.search(&5).unwrap(), 5);
+    }
+
+    // Compacting a tree after removals should keep every remaining key
+    // reachable and in order.
+    #[test]
+    fn compact() {
+        let mut tree = BinarySearchTree::new();
+        for i in 0..100 {
+            let key = calculate_hash(&i);
+            tree.insert(key, i);
+        }
+        for i in (0..100).filter(|i| i % 4 != 0) {
+            tree.remove(&calculate_hash(&i));
+        }
+
+        let before: Vec<i32> = tree.as_vector().into_iter().copied().collect();
+        tree.compact();
+        let after: Vec<i32> = tree.as_vector().into_iter().copied().collect();
+        assert_eq!(before, after);
+        for i in (0..100).filter(|i| i % 4 == 0) {
+            assert_eq!(*tree.search(&calculate_hash(&i)).unwrap(), i);
+        }
+
+        // The compacted tree keeps working.
+        tree.insert(calculate_hash(&1), 1);
+        assert_eq!(*tree.search(&calculate_hash(&1)).unwrap(), 1);
+    }
+
+    // A tree restored from its encoded form should contain the same nodes,
+    // linked the same way.
+    #[test]
+    fn encode_and_decode() {
+        use crate::encoding;
+
+        let mut tree = BinarySearchTree::new();
+        for i in 0..100 {
+            tree.insert(calculate_hash(&i), i.to_string());
+        }
+        for i in (0..100).filter(|i| i % 3 == 0) {
+            tree.remove(&calculate_hash(&i));
+        }
+
+        let bytes = encoding::to_bytes(&tree);
+        let mut restored: BinarySearchTree<u64, String> = encoding::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.as_vector(), tree.as_vector());
+        for i in (0..100).filter(|i| i % 3 != 0) {
+            assert_eq!(*restored.search(&calculate_hash(&i)).unwrap(), i.to_string());
+            assert_eq!(restored.remove(&calculate_hash(&i)), Some(i.to_string()));
+        }
+        assert!(restored.as_vector().is_empty());
+    }
+
+    // Build the tree        5
+    //                    3   8
+    //                   1 4
+    // and return it with the arena keys of its nodes, in the order above.
+    fn small_tree() -> (BinarySearchTree<i32, ()>, Vec<NodeKey<i32, ()>>) {
+        let mut tree = BinarySearchTree::new();
+        for i in [5, 3, 8, 1, 4] {
+            tree.insert(i, ());
+        }
+        let root = tree.root.unwrap();
+        let keys = [5, 3, 8, 1, 4].iter().
+            map(|i| tree.search_internal(root, i).unwrap()).
+            collect();
+        (tree, keys)
+    }
+
+    #[test]
+    fn validate() {
+        let mut tree = BinarySearchTree::new();
+        assert_eq!(tree.validate(), Ok(()));
+        for i in 0..100 {
+            tree.insert(calculate_hash(&i), i);
+        }
+        for i in (0..100).step_by(4) {
+            tree.remove(&calculate_hash(&i));
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        tree.compact();
+        assert_eq!(tree.validate(), Ok(()));
+
+        // 4 is in 5's left subtree, so it can't be changed to 6.
+        let (mut tree, keys) = small_tree();
+        tree.nodes[keys[4]].key = 6;
+        assert_eq!(tree.validate(), Err(vec![TreeCorruption::OutOfOrder(keys[4].index())]));
+
+        // Cutting 3's link to 4 leaves 4 unreachable.
+        let (mut tree, keys) = small_tree();
+        tree.nodes[keys[1]].right = None;
+        assert_eq!(tree.validate(), Err(vec![TreeCorruption::Orphaned(keys[4].index())]));
+
+        // Removing 8 without unlinking it leaves 5 pointing at nothing.
+        let (mut tree, keys) = small_tree();
+        tree.nodes.remove(keys[2]);
+        assert_eq!(tree.validate(), Err(vec![TreeCorruption::DanglingLink {
+            parent: Some(keys[0].index()),
+            child: keys[2].index()
+        }]));
+
+        // Linking 1 back to the root creates a cycle.
+        let (mut tree, keys) = small_tree();
+        tree.nodes[keys[3]].right = Some(keys[0]);
+        assert!(tree.validate().unwrap_err().contains(
+            &TreeCorruption::SharedNode(keys[0].index())));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is out of order")]
+    fn validate_after_mutations() {
+        let (mut tree, keys) = small_tree();
+        tree.validate_after_mutations(true);
+        tree.remove(&1);
+        tree.insert(1, ());
+        tree.nodes[keys[4]].key = 6;
+        tree.insert(10, ());
+    }
+
     // Attempt to insert duplicate keys. Should panic.
     #[test]
     #[should_panic(expected = "Insertion of duplicate keys is not supported.")]
@@ -237,9 +391,41 @@ mod binary_search_tree_tests {
 struct Node<T, U> {
     key: T,
     data: U,
-    // Indices of the left and right child in the "arena" holding this tree.
-    left: Option<usize>,
-    right: Option<usize>
+    // Arena keys of the left and right child in the "arena" holding this tree.
+    left: Option<NodeKey<T, U>>,
+    right: Option<NodeKey<T, U>>
+}
+
+// The arena key of a node in a tree. Not to be confused with the key the node
+// is sorted by.
+type NodeKey<T, U> = Key<Node<T, U>>;
+
+// A (parent, child, is_left_child) tuple, as returned by find_with_parent().
+type ParentAndChild<T, U> = (Option<NodeKey<T, U>>, Option<NodeKey<T, U>>, Option<bool>);
+
+// A node is written as its key, data and child links.
+impl<T, U> Encode for Node<T, U>
+    where T: Encode, U: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.key.encode(out);
+        self.data.encode(out);
+        self.left.encode(out);
+        self.right.encode(out);
+    }
+}
+
+impl<T, U> Decode for Node<T, U>
+    where T: Decode, U: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        Ok(Node {
+            key: T::decode(input)?,
+            data: U::decode(input)?,
+            left: Option::decode(input)?,
+            right: Option::decode(input)?
+        })
+    }
 }
 
 /// An unbalanced binary search tree.
@@ -247,9 +433,50 @@ pub struct BinarySearchTree<T, U>
     where T: PartialOrd + Eq
 {
     nodes: Arena<Node<T, U>>,
-    root: Option<usize>
+    root: Option<NodeKey<T, U>>,
+    // Set by validate_after_mutations(). Ignored in release builds.
+    validate_on_mutation: bool
 }
 
+/// A broken structural invariant reported by BinarySearchTree::validate().
+/// Nodes are identified by their index in the tree's storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeCorruption {
+    /// The storage holding the nodes is itself inconsistent.
+    Arena(ArenaCorruption),
+    /// The root (if parent is None) or a child link of the parent node refers
+    /// to a node that doesn't exist.
+    DanglingLink { parent: Option<usize>, child: usize },
+    /// The node is linked to from more than one place, which also covers
+    /// cycles.
+    SharedNode(usize),
+    /// The node exists but can't be reached from the root.
+    Orphaned(usize),
+    /// The node's key doesn't fit between the keys of its ancestors.
+    OutOfOrder(usize)
+}
+
+impl fmt::Display for TreeCorruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeCorruption::Arena(e) =>
+                write!(f, "node storage: {}", e),
+            TreeCorruption::DanglingLink { parent: None, child } =>
+                write!(f, "the root refers to missing node {}", child),
+            TreeCorruption::DanglingLink { parent: Some(parent), child } =>
+                write!(f, "node {} links to missing node {}", parent, child),
+            TreeCorruption::SharedNode(index) =>
+                write!(f, "node {} is reachable through more than one link", index),
+            TreeCorruption::Orphaned(index) =>
+                write!(f, "node {} can't be reached from the root", index),
+            TreeCorruption::OutOfOrder(index) =>
+                write!(f, "node {} is out of order with its ancestors", index)
+        }
+    }
+}
+
+impl Error for TreeCorruption {}
+
 impl<T, U> Node<T,U>
     where T: PartialOrd + Eq
 {
@@ -272,7 +499,8 @@ impl<T, U> BinarySearchTree<T, U>
     pub fn new() -> BinarySearchTree<T, U> {
         BinarySearchTree {
             nodes: Arena::new(),
-            root: None
+            root: None,
+            validate_on_mutation: false
         }
     }
 
@@ -286,13 +514,14 @@ impl<T, U> BinarySearchTree<T, U>
             Some(root) => self.insert_internal(root, new_node),
             None => self.root = Some(self.nodes.insert(new_node))
         }
+        self.check_invariants();
     }
 
     // Recursively search for the proper location for the new node and insert it.
     // The caller must ensure that the "current" parameter indexes to an existing Node.
-    fn insert_internal(&mut self, current: usize, new_node: Node<T, U>) {
+    fn insert_internal(&mut self, current: NodeKey<T, U>, new_node: Node<T, U>) {
         let next_is_left;
-        let current_node = match &self.nodes[current] {
+        let current_node = match self.nodes.borrow(current) {
             Some(node) => {
                 node
             },
@@ -347,7 +576,7 @@ impl<T, U> BinarySearchTree<T, U>
             Some(root) => match self.search_internal(root, key) {
                 // We can unwrap here because search_internal is expected to
                 // either return a valid index or None.
-                Some(i) => Some(&(self.nodes[i].as_ref().unwrap().data)),
+                Some(i) => Some(&(self.nodes.borrow(i).unwrap().data)),
                 None => None
             }
             None => None
@@ -356,10 +585,10 @@ impl<T, U> BinarySearchTree<T, U>
 
     // Find the node with the given key and return its index. If no such node
     // exists, return None.
-    fn search_internal(&self, current: usize, key: &T)
-        -> Option<usize>
+    fn search_internal(&self, current: NodeKey<T, U>, key: &T)
+        -> Option<NodeKey<T, U>>
     {
-        let current_node = match &self.nodes[current] {
+        let current_node = match self.nodes.borrow(current) {
             Some(node) => node,
             None => {
                 panic!("An invalid node index was passed to search_internal().\
@@ -434,56 +663,25 @@ impl<T, U> BinarySearchTree<T, U>
             // We can ignore the possibility that the node has no true successor
             // because we know the node has a right child, meaning it must have
             // a successor.
-            let i_successor = self.successor(i_delete);
-            let successor_node = self.nodes[i_successor].as_ref().unwrap();
-            let key_successor = &(successor_node.key);
-            let (s_parent, _successor, s_is_left) = 
-                self.find_with_parent(i_root, None, key_successor);
+            let (s_parent, i_successor) =
+                self.leftmost_with_parent(doomed_node.right.unwrap());
 
             // Remove the pointers to the successor...
-            // Successor is the doomed node's child (it can only be a right child).
-            if doomed_node.right.unwrap() == i_successor {
-                // If the doomed_node is the parent of its successor, there's
-                // no need to change the child pointer of the successor's
-                // parent, because that parent is itself being deleted. The
-                // "Splice the successor back in" step below will do all that
-                // is needed in this case.
-            }
-            // Successor has one child.
-            else if doomed_node.left.is_some() || doomed_node.right.is_some() {
-                    self.remove_single_child_case(i_successor,
-                                             &successor_node,
-                                             s_parent,
-                                             s_is_left);
-            }
-            // Successor is a leaf. Remove its parent's pointer to it.
-            else {
-                if let Some(i_s_parent) = s_parent {
-                   let parent_node = self.nodes.borrow_mutable(i_s_parent).
-                       expect("find_with_parent() should return a valid parent index");
-                   if let Some(is_left) = s_is_left {
-                       if is_left {
-                           parent_node.left = None;
-                       }
-                       else {
-                           parent_node.right = None;
-                       }
-                   }
-                   else {
-                       panic!("If find_with_parent() returns a parent, it should \
-                            also return a valid \"is left child\" boolean.");
-                   }
-                }
-                else {
-                    // If a node has two nodes, and therefore has a right child,
-                    // its successor must exist and must be in its right subtree,
-                    // meaning its successor must have a parent.
-                    panic!("It shouldn't be possible for the successor of a \
-                        node with two children to be the root. This is a bug in \
-                        BinarySearchTree");
-                }
+            // If the doomed node is the parent of its successor (i.e. the
+            // successor is its right child), there's no need to change the child
+            // pointer of the successor's parent, because that parent is itself
+            // being deleted. The "Splice the successor back in" step below will
+            // do all that is needed in this case.
+            // Otherwise, the successor is the left child of its parent and has
+            // at most a right child, which takes the successor's place.
+            if let Some(i_s_parent) = s_parent {
+                let successor_right = self.nodes.borrow(i_successor).
+                    expect("i_successor should be valid. There is a bug in BinarySearchTree.").
+                    right;
+                let parent_node = self.nodes.borrow_mutable(i_s_parent).
+                    expect("leftmost_with_parent() should return a valid parent node key");
+                parent_node.left = successor_right;
             }
- 
 
             // Splice the successor back in where the doomed node was.
             match parent {
@@ -512,17 +710,15 @@ impl<T, U> BinarySearchTree<T, U>
             let successor_node = self.nodes.borrow_mutable(i_successor);
             let successor_node = successor_node.
                 expect("i_successor should be valid. There is a bug in BinarySearchTree.");
-            match doomed_node.left {
-                Some(i_left) => successor_node.left = Some(i_left),
-                None => successor_node.left = None
-            }
-            match doomed_node.right {
-                Some(i_right) => successor_node.right = Some(i_right),
-                None => successor_node.right = None
+            successor_node.left = doomed_node.left;
+            // A successor that was the doomed node's right child keeps its own
+            // right subtree.
+            if s_parent.is_some() {
+                successor_node.right = doomed_node.right;
             }
         }
         else if doomed_node.left.is_some() || doomed_node.right.is_some() {
-            self.remove_single_child_case(i_delete, &doomed_node, parent, is_left_child);
+            self.remove_single_child_case(&doomed_node, parent, is_left_child);
         }
         else {
             // 1. Deleting a leaf node: Just delete it.
@@ -538,91 +734,57 @@ impl<T, U> BinarySearchTree<T, U>
                     parent_node.right = None;
                 }
             }
+            else {
+                // We are deleting the only node. The tree is now empty.
+                self.root = None;
+            }
         }
 
+        self.check_invariants();
         // Return the contents of the deleted node.
         Some(doomed_node.data)
     }
 
     // Helper function for remove():
-    //  2. Deleting a node with one (left) child: Make the parent of the
-    //  deleted node point to the child node.
+    //  2. Deleting a node with one child: Make the parent of the deleted node
+    //  point to the child node.
+    // The doomed node has already been taken out of the arena, so its child
+    // node key is read from the node itself.
     fn remove_single_child_case(
         &mut self,
-        i_delete: usize,
         doomed_node: &Node<T, U>,
-        parent: Option<usize>,
+        parent: Option<NodeKey<T, U>>,
         is_left_child: Option<bool>)
     {
-        if doomed_node.left.is_some() {
-            match parent {
-                Some(i_parent) => {
-                    let parent_node = self.nodes.
-                        borrow_mutable(i_parent).
-                        expect("i_parent should be valid. There is a bug in BinarySearchTree.");
-                    // If the doomed node is its parent's left child.
-                    if is_left_child.
-                        expect("If the node has a parent, it must be a child") {
-                        parent_node.left =
-                            Some(self.nodes[i_delete].
-                                as_ref().
-                                expect("i_delete should be valid. There is a bug in BinarySearchTree").
-                                left.
-                                unwrap());
-                    }
-                    else { // If the doomed node is its parent's right child.
-                        parent_node.right =
-                            Some(self.nodes[i_delete].
-                                 as_ref().
-                                 expect("i_delete should be valid. There is a bug in BinarySearchTree").
-                                 left.
-                                 unwrap());
-                    }
+        let child = match (doomed_node.left, doomed_node.right) {
+            (Some(left), None) => left,
+            (None, Some(right)) => right,
+            _ => {
+                panic!("remove_single_child_case() should not be called unless the \
+                        doomed node has exactly one child. This is a bug in \
+                        BinarySearchTree.");
+            }
+        };
 
-                },
-                None => {
-                    // We are deleting the root. Make the child the new root.
-                    self.root = doomed_node.left;
+        match parent {
+            Some(i_parent) => {
+                let parent_node = self.nodes.
+                    borrow_mutable(i_parent).
+                    expect("i_parent should be valid. There is a bug in BinarySearchTree.");
+                // If the doomed node is its parent's left child.
+                if is_left_child.
+                    expect("If the node has a parent, it must be a child") {
+                    parent_node.left = Some(child);
                 }
-            }
-        }
-        else if doomed_node.right.is_some() {
-            match parent {
-                Some(i_parent) => {
-                    let parent_node = self.nodes.
-                        borrow_mutable(i_parent).
-                        expect("i_parent should be valid. There is a bug in BinarySearchTree.");
-                    // If the doomed node is its parent's left child.
-                    if *is_left_child.
-                        as_ref().
-                        expect("If the node has a parent, it must be a child") {
-                        parent_node.left =
-                            Some(self.nodes[i_delete].
-                                 as_ref().
-                                 expect("i_delete should be valid. There is a bug in BinarySearchTree").
-                                 right.
-                                 unwrap());
-                    }
-                    else { // If the doomed node is its parent's right child.
-                        parent_node.right =
-                            Some(self.nodes[i_delete].
-                                 as_ref().
-                                 expect("i_parent should be valid. There is a bug in BinarySearchTree.").
-                                 right.
-                                 unwrap());
-                    }
-                },
-                None => {
-                    // We are deleting the root. Make the child the new root.
-                    self.root = doomed_node.right;
+                else { // If the doomed node is its parent's right child.
+                    parent_node.right = Some(child);
                 }
+            },
+            None => {
+                // We are deleting the root. Make the child the new root.
+                self.root = Some(child);
             }
         }
-        else {
-            panic!("remove_single_child_case() should not be called unless the \
-                    doomed node has exactly one child. This is a bug in \
-                    BinarySearchTree.");
-        }
     }
 
     // Find the node with the given key and return its index, the index of its
@@ -632,10 +794,10 @@ impl<T, U> BinarySearchTree<T, U>
     // value will be none if there is no parent.
     // Initially, current should be the index of the root and previous should be
     // None. If the tree is empty (has no root), this function sould not be called.
-    fn find_with_parent(&self, current: usize, previous: Option<usize>, key: &T)
-        -> (Option<usize>, Option<usize>, Option<bool>)
+    fn find_with_parent(&self, current: NodeKey<T, U>, previous: Option<NodeKey<T, U>>, key: &T)
+        -> ParentAndChild<T, U>
     {
-        let current_node = match &self.nodes[current] {
+        let current_node = match self.nodes.borrow(current) {
             Some(node) => node,
             None => {
                 panic!("An invalid node index was passed to find_with_parent().\
@@ -667,8 +829,7 @@ impl<T, U> BinarySearchTree<T, U>
                 Some(i_parent) => {
                     let is_left_child;
                     let parent = self.
-                        nodes[i_parent].
-                        as_ref().
+                        nodes.borrow(i_parent).
                         expect("i_parent should be valid. There is a bug in BinarySearchTree.");
                     is_left_child =
                         parent.left.is_some() && parent.left.unwrap() == current;
@@ -679,41 +840,123 @@ impl<T, U> BinarySearchTree<T, U>
         }
     }
 
-    // Return the index of the successor of the node at the given index.
-    // Assumes the index argument is a valid index.
-    // If the node at index has no successor, it will be returned as its own
-    // successor.
-    fn successor(&self, index: usize) -> usize {
-        let mut current;
-        
-        // Get the index of the right subtree if it exists. Otherwise, return
-        // the given index as its own successor.
-        match self.
-            nodes[index].
-            as_ref().
-            expect("invalid index in BinaryTree.Successor()").
-            right {
+    // Return the arena key of the leftmost node in the subtree rooted at the
+    // given node, along with the arena key of that node's parent. The parent will be
+    // None if the given node is itself the leftmost node.
+    // Assumes the index argument is a valid node key.
+    fn leftmost_with_parent(&self, index: NodeKey<T, U>) -> (Option<NodeKey<T, U>>, NodeKey<T, U>) {
+        let mut parent = None;
+        let mut current = index;
 
-            Some(right) => current = right,
-            None => return index
-        }
-        
         while let Some(left) =
             self.
-            nodes[current].
-            as_ref().
-            expect("invalid index in BinaryTree.Successor()").
+            nodes.borrow(current).
+            expect("invalid node key in BinarySearchTree.leftmost_with_parent()").
             left {
 
+            parent = Some(current);
             current = left;
         }
 
-        current
+        (parent, current)
+    }
+
+    /// Release the memory held by removed nodes. Nodes are moved to the front
+    /// of the tree's storage and their child links are rewritten to match.
+    pub fn compact(&mut self) {
+        let remapping = self.nodes.compact_with(|node, remapping| {
+            node.left = node.left.map(|left| remapping.get(left).
+                expect("A child link should refer to a live node. There is a bug in BinarySearchTree."));
+            node.right = node.right.map(|right| remapping.get(right).
+                expect("A child link should refer to a live node. There is a bug in BinarySearchTree."));
+        });
+        self.root = self.root.map(|root| remapping.get(root).
+            expect("The root should be a live node. There is a bug in BinarySearchTree."));
+        self.check_invariants();
+    }
+
+    /// Check the tree's structure: the node storage must be consistent, every
+    /// link must lead to an existing node, every node must be reachable from
+    /// the root through exactly one link, and the keys must be in search tree
+    /// order. Returns every problem found.
+    pub fn validate(&self) -> Result<(), Vec<TreeCorruption>> {
+        let mut problems: Vec<TreeCorruption> = match self.nodes.validate() {
+            Ok(()) => Vec::new(),
+            Err(problems) => problems.into_iter().map(TreeCorruption::Arena).collect()
+        };
+
+        // Walk the tree from the root. Each entry on the stack holds the
+        // index of the parent, the node, and the (exclusive) bounds its key
+        // has to fall between.
+        let mut reached = SecondaryMap::new();
+        let mut stack = Vec::new();
+        if let Some(root) = self.root {
+            stack.push((None, root, None::<&T>, None::<&T>));
+        }
+        while let Some((parent, current, lower, upper)) = stack.pop() {
+            let node = match self.nodes.get(current) {
+                Some(node) => node,
+                None => {
+                    problems.push(TreeCorruption::DanglingLink { parent, child: current.index() });
+                    continue;
+                }
+            };
+            // Don't walk a subtree twice. If there's a cycle, this is also
+            // what ends the walk.
+            if reached.insert(current, ()).is_some() {
+                problems.push(TreeCorruption::SharedNode(current.index()));
+                continue;
+            }
+
+            let above_lower = lower.is_none_or(|lower| *lower < node.key);
+            let below_upper = upper.is_none_or(|upper| node.key < *upper);
+            if !above_lower || !below_upper {
+                problems.push(TreeCorruption::OutOfOrder(current.index()));
+            }
+
+            if let Some(left) = node.left {
+                stack.push((Some(current.index()), left, lower, Some(&node.key)));
+            }
+            if let Some(right) = node.right {
+                stack.push((Some(current.index()), right, Some(&node.key), upper));
+            }
+        }
+
+        for (key, _) in self.nodes.iter() {
+            if !reached.contains_key(key) {
+                problems.push(TreeCorruption::Orphaned(key.index()));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(problems)
+        }
+    }
+
+    /// In debug builds, run validate() after every insert, remove and
+    /// compaction, and panic if it fails. Each check takes O(n) time. Does
+    /// nothing in release builds.
+    pub fn validate_after_mutations(&mut self, enabled: bool) {
+        self.validate_on_mutation = enabled;
+    }
+
+    // Panic with validate()'s report if validate_after_mutations() is on.
+    fn check_invariants(&self) {
+        if cfg!(debug_assertions) && self.validate_on_mutation {
+            if let Err(problems) = self.validate() {
+                let report: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                panic!("The tree was corrupted by a mutation: {}. This is a bug in \
+                    BinarySearchTree.", report.join("; "));
+            }
+        }
     }
 
     /// Get an iterator to perform an in-order traversal on the tree, returning
     /// a reference to the data stored in the next node with each iteration.
-    pub fn iter_in_order(&self) -> InOrderIterator<T, U> {
+    pub fn iter_in_order(&self) -> InOrderIterator<'_, T, U> {
         InOrderIterator {
             tree: self,
             stack: Vec::new(),
@@ -743,7 +986,7 @@ impl<T, U> BinarySearchTree<T, U>
             // Keep moving left and pushing the leftmost node to the stack.
             while let Some(cur) = current {
                 stack.push(cur);
-                current = match &self.nodes[cur] {
+                current = match self.nodes.borrow(cur) {
                     Some(node) => match node.left {
                         Some(left) => Some(left),
                         None => None
@@ -761,9 +1004,9 @@ impl<T, U> BinarySearchTree<T, U>
                 let cur = stack.pop().unwrap();
                 // We can unwrap here. If cur was invalid, we would've panicked
                 // in the previous loop.
-                vector.push(&(self.nodes[cur].as_ref().unwrap().data));
+                vector.push(&(self.nodes.borrow(cur).unwrap().data));
                 // Go right.
-                current = match &self.nodes[cur] {
+                current = match self.nodes.borrow(cur) {
                     Some(node) => match node.right {
                         Some(right) => Some(right),
                         None => None
@@ -780,12 +1023,38 @@ impl<T, U> BinarySearchTree<T, U>
     }
 }
 
+/// Writes the tree's node arena and root. Node keys in the arena are
+/// preserved, so the child links still hold when the tree is read back.
+impl<T, U> Encode for BinarySearchTree<T, U>
+    where T: PartialOrd + Eq + Encode, U: Encode
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.nodes.encode(out);
+        self.root.encode(out);
+    }
+}
+
+impl<T, U> Decode for BinarySearchTree<T, U>
+    where T: PartialOrd + Eq + Decode, U: Decode
+{
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError> {
+        let nodes: Arena<Node<T, U>> = Arena::decode(input)?;
+        let root: Option<NodeKey<T, U>> = Option::decode(input)?;
+
+        let tree = BinarySearchTree { nodes, root, validate_on_mutation: false };
+        if let Err(problems) = tree.validate() {
+            return Err(DecodeError::InvalidData(problems[0].to_string()));
+        }
+        Ok(tree)
+    }
+}
+
 pub struct InOrderIterator<'a, T, U>
     where T: PartialOrd + Eq
 {
     tree: &'a BinarySearchTree<T, U>,
-    stack: Vec<usize>,
-    current: Option<usize>,
+    stack: Vec<NodeKey<T, U>>,
+    current: Option<NodeKey<T, U>>,
     go_left: bool
 }
 
@@ -812,7 +1081,7 @@ impl<'a, T, U> Iterator for InOrderIterator<'a, T, U>
                 // Keep moving left and pushing the leftmost node to the stack.
                 while let Some(cur) = self.current {
                     self.stack.push(cur);
-                    self.current = match &self.tree.nodes[cur] {
+                    self.current = match self.tree.nodes.borrow(cur) {
                         Some(node) => match node.left {
                             Some(left) => Some(left),
                             None => None
@@ -832,9 +1101,9 @@ impl<'a, T, U> Iterator for InOrderIterator<'a, T, U>
                 let cur = self.stack.pop().unwrap();
                 // We can unwrap here. If cur was invalid, we would've panicked
                 // in the while loop.
-                to_return = Some(&(self.tree.nodes[cur].as_ref().unwrap().data));
+                to_return = Some(&(self.tree.nodes.borrow(cur).unwrap().data));
                 // Go right.
-                self.current = match &self.tree.nodes[cur] {
+                self.current = match self.tree.nodes.borrow(cur) {
                     Some(node) => match node.right {
                         Some(right) => {
                             self.go_left = true;